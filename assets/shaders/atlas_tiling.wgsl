@@ -0,0 +1,33 @@
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+
+@group(2) @binding(100) var<uniform> tile_step: f32;
+
+@fragment
+fn fragment(in: VertexOutput, @builtin(front_facing) is_front: bool) -> FragmentOutput {
+    // uv is measured in tiles across the quad, uv_b is the corner of the tile in the atlas
+    var tiled = in;
+    tiled.uv = in.uv_b + fract(in.uv) * tile_step;
+
+    // the vertex color carries voxel light rather than a tint, sky light in red and block light in green,
+    // every missing level dims the surface by a constant factor, blue is the baked corner occlusion
+    var brightness = 1.0;
+#ifdef VERTEX_COLORS
+    let level = max(in.color.r, in.color.g) * 15.0;
+    brightness = pow(0.8, 15.0 - level) * mix(0.45, 1.0, in.color.b);
+    tiled.color = vec4(1.0);
+#endif
+
+    var pbr_input = pbr_input_from_standard_material(tiled, is_front);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+    pbr_input.material.base_color = vec4(pbr_input.material.base_color.rgb * brightness, pbr_input.material.base_color.a);
+
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+
+    return out;
+}
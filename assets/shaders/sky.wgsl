@@ -0,0 +1,40 @@
+#import bevy_pbr::{
+    forward_io::VertexOutput,
+    mesh_view_bindings::view,
+}
+
+@group(2) @binding(0) var<uniform> sun_direction: vec3<f32>;
+@group(2) @binding(1) var<uniform> sun_color: vec4<f32>;
+@group(2) @binding(2) var<uniform> zenith_color: vec4<f32>;
+@group(2) @binding(3) var<uniform> horizon_color: vec4<f32>;
+@group(2) @binding(4) var<uniform> night_color: vec4<f32>;
+
+fn hash(cell: vec3<f32>) -> f32 {
+    return fract(sin(dot(cell, vec3(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the sphere follows the camera, so only the direction to the fragment matters
+    let direction = normalize(in.world_position.xyz - view.world_position);
+    let height = max(direction.y, 0.0);
+    let toward_sun = dot(direction, sun_direction);
+
+    // same horizon fade as the clock uses to dim the sun
+    let daylight = smoothstep(-0.1, 0.2, sun_direction.y);
+    let day = mix(horizon_color.rgb, zenith_color.rgb, sqrt(height));
+    var color = mix(night_color.rgb, day, daylight);
+
+    // the sky around the sun picks up its color while it is low, giving dawn and dusk their glow
+    let low_sun = 1.0 - smoothstep(0.0, 0.4, abs(sun_direction.y));
+    color += sun_color.rgb * pow(max(toward_sun, 0.0), 8.0) * low_sun * 0.6;
+
+    // stars are scattered over a grid of directions and fade out as the sun comes up
+    let star = step(0.9985, hash(floor(direction * 250.0)));
+    color += vec3(star) * (1.0 - daylight) * smoothstep(0.0, 0.15, direction.y);
+
+    color = mix(color, sun_color.rgb * 3.0, smoothstep(0.9990, 0.9994, toward_sun));
+    color = mix(color, vec3(0.85, 0.87, 0.95), smoothstep(0.9994, 0.9997, -toward_sun));
+
+    return vec4(color, 1.0);
+}
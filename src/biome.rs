@@ -0,0 +1,177 @@
+use crate::config::world::SEA_LEVEL;
+
+/// the kind of land a column belongs to, land biomes come from the climate and beaches from where that
+/// land meets the sea
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Ocean,
+    Beach,
+    Plains,
+    Desert,
+    Forest,
+    Mountains,
+}
+
+/// temperature and humidity at a column, both roughly between -1 and 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    pub temperature: f64,
+    pub humidity: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerrainColumn {
+    /// world y of the topmost solid voxel
+    pub height: i32,
+    pub biome: Biome,
+}
+
+/// how far apart two climates can be and still mix their heights, smaller makes steeper borders
+const BLEND_WIDTH: f64 = 0.12;
+/// land this close to sea level is beach, unless it's a mountain
+const BEACH_HEIGHT: std::ops::RangeInclusive<i32> = SEA_LEVEL - 3..=SEA_LEVEL + 1;
+
+impl Biome {
+    /// the highest tree density of any biome, a roll above it grows no tree whatever the column
+    pub const MAX_TREE_DENSITY: f64 = 0.04;
+    /// the biomes the climate picks between, beach only appears afterwards
+    const CLIMATE: [Self; 5] = [Self::Ocean, Self::Plains, Self::Desert, Self::Forest, Self::Mountains];
+
+    /// the climate the biome is at its strongest in, a column belongs to the nearest one
+    fn climate(self) -> Climate {
+        let (temperature, humidity) = match self {
+            | Self::Ocean | Self::Beach => (0.2, 0.45),
+            | Self::Plains => (0., -0.15),
+            | Self::Desert => (0.4, -0.3),
+            | Self::Forest => (-0.1, 0.25),
+            | Self::Mountains => (-0.5, -0.25),
+        };
+        Climate { temperature, humidity }
+    }
+
+    /// base height and the height added on top at full roughness
+    fn relief(self) -> (f64, f64) {
+        match self {
+            | Self::Ocean => (SEA_LEVEL as f64 - 18., 10.),
+            | Self::Beach => (SEA_LEVEL as f64, 2.),
+            | Self::Plains => (SEA_LEVEL as f64 + 2., 10.),
+            | Self::Desert => (SEA_LEVEL as f64 + 2., 8.),
+            | Self::Forest => (SEA_LEVEL as f64 + 3., 16.),
+            | Self::Mountains => (SEA_LEVEL as f64 + 10., 60.),
+        }
+    }
+
+    /// the lowest and highest any surface reaches, at no roughness and at full roughness
+    pub fn surface_range() -> std::ops::RangeInclusive<i32> {
+        let reliefs = Self::CLIMATE.map(Self::relief);
+        let lowest = reliefs.iter().map(|&(base, _)| base).fold(f64::INFINITY, f64::min);
+        let highest =
+            reliefs.iter().map(|&(base, amplitude)| base + amplitude).fold(f64::NEG_INFINITY, f64::max);
+
+        lowest.floor() as i32..=highest.ceil() as i32
+    }
+
+    /// chance of a tree growing on any grass column
+    pub fn tree_density(self) -> f64 {
+        match self {
+            | Self::Forest => Self::MAX_TREE_DENSITY,
+            | Self::Plains => 0.003,
+            | _ => 0.,
+        }
+    }
+
+    /// how many voxels of subsurface block sit under the surface before stone, at the least
+    pub fn subsurface_depth(self) -> i32 {
+        match self {
+            | Self::Desert | Self::Beach => 3,
+            | Self::Mountains => 0,
+            | _ => 1,
+        }
+    }
+}
+
+impl Climate {
+    fn distance(self, other: Self) -> f64 {
+        (self.temperature - other.temperature).hypot(self.humidity - other.humidity)
+    }
+}
+
+/// picks the biome of a column and its height, roughness is the local terrain noise between 0 and about 1
+/// and is shared by every biome, the height is every biome's relief weighted by how close the climate is to
+/// it so that crossing a border ramps from one height to the other instead of stepping
+pub fn terrain_column(climate: Climate, roughness: f64) -> TerrainColumn {
+    let mut nearest = (Biome::Plains, f64::INFINITY);
+    let (mut height, mut total_weight) = (0., 0.);
+    for biome in Biome::CLIMATE {
+        let distance = climate.distance(biome.climate());
+        if distance < nearest.1 {
+            nearest = (biome, distance);
+        }
+
+        let weight = (-(distance / BLEND_WIDTH).powi(2)).exp();
+        let (base, amplitude) = biome.relief();
+        height += (base + amplitude * roughness) * weight;
+        total_weight += weight;
+    }
+    // far from every biome the weights underflow, the nearest one alone decides then
+    let height = match total_weight > f64::MIN_POSITIVE {
+        | true => height / total_weight,
+        | false => {
+            let (base, amplitude) = nearest.0.relief();
+            base + amplitude * roughness
+        }
+    };
+
+    let height = height as i32;
+    let biome = match nearest.0 {
+        | Biome::Mountains => Biome::Mountains,
+        | _ if height < *BEACH_HEIGHT.start() => Biome::Ocean,
+        | _ if BEACH_HEIGHT.contains(&height) => Biome::Beach,
+        | biome => biome,
+    };
+
+    TerrainColumn { height, biome }
+}
+
+#[cfg(test)]
+mod biome_selection {
+    use super::*;
+
+    #[test]
+    fn climate_center_selects_biome() {
+        for biome in [Biome::Plains, Biome::Desert, Biome::Forest, Biome::Mountains] {
+            assert!(terrain_column(biome.climate(), 0.5).biome == biome);
+        }
+        assert!(terrain_column(Biome::Ocean.climate(), 0.).biome == Biome::Ocean);
+    }
+
+    fn between(from: Biome, to: Biome, t: f64) -> Climate {
+        let [from, to] = [from.climate(), to.climate()];
+        Climate {
+            temperature: from.temperature + (to.temperature - from.temperature) * t,
+            humidity: from.humidity + (to.humidity - from.humidity) * t,
+        }
+    }
+
+    #[test]
+    fn beach_between_land_and_ocean() {
+        let biomes: Vec<Biome> = (0..=100)
+            .map(|step| terrain_column(between(Biome::Plains, Biome::Ocean, step as f64 / 100.), 0.3).biome)
+            .collect();
+        let beach = biomes.iter().position(|&biome| biome == Biome::Beach).unwrap();
+        assert!(biomes[..beach].iter().all(|&biome| !matches!(biome, Biome::Beach | Biome::Ocean)));
+        assert!(biomes[beach..].iter().all(|&biome| matches!(biome, Biome::Beach | Biome::Ocean)));
+        assert!(biomes.last() == Some(&Biome::Ocean));
+    }
+
+    #[test]
+    fn heights_ramp_across_borders() {
+        let mut previous = terrain_column(Biome::Plains.climate(), 0.5).height;
+        for step in 1..=100 {
+            let climate = between(Biome::Plains, Biome::Mountains, step as f64 / 100.);
+            let height = terrain_column(climate, 0.5).height;
+            assert!((height - previous).abs() <= 3);
+            previous = height;
+        }
+    }
+}
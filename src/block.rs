@@ -1,4 +1,19 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
 use bevy::math::UVec2;
+use bevy::prelude::Resource;
+use serde::Deserialize;
+
+use crate::chunk::MAX_LIGHT;
+use crate::config::aesthetics::ATLAS_SIZE;
+use crate::config::aesthetics::TEXTURE_SIZE;
 
 trait _Block
 where
@@ -9,134 +24,366 @@ where
     fn parameters() -> Self::Output;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Voxel {
+    #[default]
     Empty,
-    Full(BlockType),
-    Semi(BlockType),
+    Full(BlockId),
+    Semi(BlockId),
 }
 
-impl Default for Voxel {
-    fn default() -> Self {
-        Self::Full(BlockType::default())
+impl Voxel {
+    pub const fn block(&self) -> Option<BlockId> {
+        match self {
+            | Self::Full(block) | Self::Semi(block) => Some(*block),
+            | Self::Empty => None,
+        }
     }
 }
 
-impl From<Voxel> for String {
-    fn from(val: Voxel) -> Self {
-        match val {
-            | Voxel::Full(block) => String::from(block),
-            | Voxel::Semi(block) => String::from(block),
-            | _ => todo!("these blocks don't even exist yet"),
+/// position of a block in the [`BlockRegistry`] it came from, ids are handed out at startup and can change
+/// whenever the definitions do, so they never leave the running game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(u16);
+
+/// full blocks are opaque cubes that hide the faces behind them, semi blocks are drawn in the transparent pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BlockClass {
+    Full,
+    Semi,
+}
+
+impl BlockClass {
+    const fn prefix(&self) -> &'static str {
+        match self {
+            | Self::Full => "full",
+            | Self::Semi => "semi",
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum BlockType {
-    Grass,
-    Dirt,
-    Sand,
-    Wood,
-    Leaf,
-    Stone,
-    Plank,
-    Coal,
-    Water,
+/// tile coordinates in the texture atlas for each side of the block
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(from = "TextureTiles")]
+pub struct BlockTexture {
+    pub top: UVec2,
+    pub bottom: UVec2,
+    pub side: UVec2,
 }
 
-impl Default for BlockType {
-    fn default() -> Self {
-        Self::Grass
-    }
-}
-
-pub const BLOCKS: [Voxel; 9] = [
-    Voxel::Full(BlockType::Grass),
-    Voxel::Full(BlockType::Dirt),
-    Voxel::Full(BlockType::Sand),
-    Voxel::Full(BlockType::Wood),
-    Voxel::Semi(BlockType::Leaf),
-    Voxel::Full(BlockType::Stone),
-    Voxel::Full(BlockType::Plank),
-    Voxel::Full(BlockType::Coal),
-    Voxel::Semi(BlockType::Water),
-];
-
-pub fn get_block(index: usize) -> Voxel {
-    BLOCKS[index % BLOCKS.len()]
-}
-
-impl From<BlockType> for String {
-    fn from(value: BlockType) -> Self {
-        match value {
-            | BlockType::Grass => String::from("grass"),
-            | BlockType::Dirt => String::from("dirt"),
-            | BlockType::Sand => String::from("sand"),
-            | BlockType::Wood => String::from("wood"),
-            | BlockType::Leaf => String::from("leaf"),
-            | BlockType::Stone => String::from("stone"),
-            | BlockType::Plank => String::from("plank"),
-            | BlockType::Coal => String::from("coal"),
-            | BlockType::Water => String::from("water"),
+#[derive(Deserialize)]
+struct TextureTiles {
+    top: (u32, u32),
+    bottom: (u32, u32),
+    side: (u32, u32),
+}
+
+impl From<TextureTiles> for BlockTexture {
+    fn from(tiles: TextureTiles) -> Self {
+        Self { top: tiles.top.into(), bottom: tiles.bottom.into(), side: tiles.side.into() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockDefinition {
+    pub name: String,
+    pub textures: BlockTexture,
+    pub class: BlockClass,
+    pub collision: bool,
+    /// light swallowed on top of the one level every step costs, full opacity blocks light entirely
+    pub opacity: u8,
+    #[serde(default)]
+    pub emission: u8,
+    /// fluids flow into the empty space around them, see [`crate::fluid`]
+    #[serde(default)]
+    pub fluid: bool,
+}
+
+/// a voxel written out as text, `air` or the block's class and name such as `full:stone` and `semi:water`,
+/// parsing only checks the shape of the name, the registry decides whether the block exists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoxelName {
+    Air,
+    Block { class: BlockClass, name: String },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VoxelNameError {
+    Empty,
+    MissingClass(String),
+    UnknownClass(String),
+    InvalidName(String),
+    UnknownBlock(String),
+    WrongClass { name: String, class: BlockClass },
+}
+
+impl fmt::Display for VoxelNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            | Self::Empty => write!(f, "voxel name is empty"),
+            | Self::MissingClass(name) => {
+                write!(f, "`{}` needs a class, write it as `full:{}` or `semi:{}`", name, name, name)
+            }
+            | Self::UnknownClass(class) => {
+                write!(f, "unknown block class `{}`, expected `full` or `semi`", class)
+            }
+            | Self::InvalidName(name) => {
+                write!(f, "block name `{}` may only contain lowercase letters and underscores", name)
+            }
+            | Self::UnknownBlock(name) => write!(f, "no block named `{}` is defined", name),
+            | Self::WrongClass { name, class } => {
+                write!(f, "`{}` is a {} block", name, class.prefix())
+            }
         }
     }
 }
 
-impl BlockType {
-    pub const fn texture_offset(&self) -> BlockTexture {
+impl Error for VoxelNameError {}
+
+fn valid_block_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|char| char.is_ascii_lowercase() || char == '_')
+}
+
+impl fmt::Display for VoxelName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            | Self::Grass => BlockTexture {
-                top: UVec2::new(4, 4),
-                bot: UVec2::new(1, 1),
-                sid: UVec2::new(4, 1),
-            },
-            | Self::Dirt => BlockTexture {
-                top: UVec2::new(1, 1),
-                bot: UVec2::new(1, 1),
-                sid: UVec2::new(1, 1),
-            },
-            | Self::Sand => BlockTexture {
-                top: UVec2::new(10, 1),
-                bot: UVec2::new(10, 1),
-                sid: UVec2::new(10, 1),
-            },
-            | Self::Wood => BlockTexture {
-                top: UVec2::new(2, 7),
-                bot: UVec2::new(2, 7),
-                sid: UVec2::new(2, 4),
-            },
-            | Self::Leaf => BlockTexture {
-                top: UVec2::new(7, 4),
-                bot: UVec2::new(7, 4),
-                sid: UVec2::new(7, 4),
-            },
-            | Self::Stone => BlockTexture {
-                top: UVec2::new(13, 1),
-                bot: UVec2::new(13, 1),
-                sid: UVec2::new(13, 1),
-            },
-            | Self::Plank => BlockTexture {
-                top: UVec2::new(10, 4),
-                bot: UVec2::new(10, 4),
-                sid: UVec2::new(10, 4),
-            },
-            | Self::Coal => BlockTexture {
-                top: UVec2::new(13, 4),
-                bot: UVec2::new(13, 4),
-                sid: UVec2::new(13, 4),
-            },
-            | Self::Water => BlockTexture {
-                top: UVec2::new(7, 1),
-                bot: UVec2::new(7, 1),
-                sid: UVec2::new(7, 1),
-            },
+            | Self::Air => write!(f, "{}", AIR),
+            | Self::Block { class, name } => write!(f, "{}:{}", class.prefix(), name),
         }
     }
 }
 
-pub struct BlockTexture {
-    pub top: UVec2,
-    pub bot: UVec2,
-    pub sid: UVec2,
+impl FromStr for VoxelName {
+    type Err = VoxelNameError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(VoxelNameError::Empty);
+        }
+        if text == AIR {
+            return Ok(Self::Air);
+        }
+
+        let Some((class, name)) = text.split_once(':')
+        else {
+            return Err(VoxelNameError::MissingClass(text.to_string()));
+        };
+        let class = match class {
+            | "full" => BlockClass::Full,
+            | "semi" => BlockClass::Semi,
+            | _ => return Err(VoxelNameError::UnknownClass(class.to_string())),
+        };
+        if !valid_block_name(name) || name == AIR {
+            return Err(VoxelNameError::InvalidName(name.to_string()));
+        }
+
+        Ok(Self::Block { class, name: name.to_string() })
+    }
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Invalid { block: String, reason: &'static str },
+    Missing(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            | Self::Io(err) => write!(f, "failed to read block definitions: {}", err),
+            | Self::Parse(err) => write!(f, "failed to parse block definitions: {}", err),
+            | Self::Invalid { block, reason } => write!(f, "block `{}` {}", block, reason),
+            | Self::Missing(block) => write!(f, "no block named `{}` is defined", block),
+        }
+    }
+}
+
+impl Error for RegistryError {}
+
+const AIR: &str = "air";
+const BUILTIN_DEFINITIONS: &str = include_str!("../assets/blocks.ron");
+
+/// every block the game knows about, cheap to clone so meshing and generation tasks can carry their own copy
+#[derive(Debug, Clone, Resource)]
+pub struct BlockRegistry {
+    definitions: Arc<[BlockDefinition]>,
+    ids: Arc<HashMap<String, BlockId>>,
+}
+
+impl BlockRegistry {
+    pub fn parse(source: &str) -> Result<Self, RegistryError> {
+        let definitions: Vec<BlockDefinition> = ron::from_str(source).map_err(RegistryError::Parse)?;
+        let tiles = (ATLAS_SIZE / TEXTURE_SIZE) as u32;
+
+        let mut ids = HashMap::new();
+        for (index, definition) in definitions.iter().enumerate() {
+            let invalid = |reason| RegistryError::Invalid { block: definition.name.clone(), reason };
+            let textures = [definition.textures.top, definition.textures.bottom, definition.textures.side];
+
+            if definition.name.is_empty() || definition.name == AIR {
+                return Err(invalid("has a reserved name"));
+            }
+            if !valid_block_name(&definition.name) {
+                return Err(invalid("name may only contain lowercase letters and underscores"));
+            }
+            if textures.iter().any(|tile| tile.x >= tiles || tile.y >= tiles) {
+                return Err(invalid("has a texture outside the atlas"));
+            }
+            if definition.opacity > MAX_LIGHT || definition.emission > MAX_LIGHT {
+                return Err(invalid("has a light value above the maximum of 15"));
+            }
+
+            let id = u16::try_from(index).map_err(|_| invalid("does not fit in the registry"))?;
+            if ids.insert(definition.name.clone(), BlockId(id)).is_some() {
+                return Err(invalid("is defined twice"));
+            }
+        }
+
+        Ok(Self { definitions: definitions.into(), ids: Arc::new(ids) })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        Self::parse(&fs::read_to_string(path).map_err(RegistryError::Io)?)
+    }
+
+    pub fn definition(&self, voxel: Voxel) -> Option<&BlockDefinition> {
+        self.definitions.get(voxel.block()?.0 as usize)
+    }
+
+    /// looks a block up by name, `air` is always defined and names the empty voxel
+    pub fn voxel(&self, name: &str) -> Option<Voxel> {
+        if name == AIR {
+            return Some(Voxel::Empty);
+        }
+        let id = *self.ids.get(name)?;
+
+        Some(match self.definitions[id.0 as usize].class {
+            | BlockClass::Full => Voxel::Full(id),
+            | BlockClass::Semi => Voxel::Semi(id),
+        })
+    }
+
+    pub fn require(&self, name: &str) -> Result<Voxel, RegistryError> {
+        self.voxel(name).ok_or_else(|| RegistryError::Missing(name.to_string()))
+    }
+
+    /// every block in definition order
+    pub fn voxels(&self) -> impl Iterator<Item = Voxel> + '_ {
+        self.definitions.iter().filter_map(|definition| self.voxel(&definition.name))
+    }
+
+    pub fn name(&self, voxel: Voxel) -> &str {
+        self.definition(voxel).map_or(AIR, |definition| &definition.name)
+    }
+
+    pub fn voxel_name(&self, voxel: Voxel) -> VoxelName {
+        match self.definition(voxel) {
+            | Some(definition) => VoxelName::Block { class: definition.class, name: definition.name.clone() },
+            | None => VoxelName::Air,
+        }
+    }
+
+    /// turns a parsed name back into a voxel, the class has to match the definition so a name that was
+    /// written for a different set of blocks isn't silently reinterpreted
+    pub fn resolve(&self, voxel_name: &VoxelName) -> Result<Voxel, VoxelNameError> {
+        let VoxelName::Block { class, name } = voxel_name
+        else {
+            return Ok(Voxel::Empty);
+        };
+        let voxel = self.voxel(name).ok_or_else(|| VoxelNameError::UnknownBlock(name.clone()))?;
+        let defined = self.definition(voxel).expect("named voxels have a definition").class;
+        if defined != *class {
+            return Err(VoxelNameError::WrongClass { name: name.clone(), class: defined });
+        }
+
+        Ok(voxel)
+    }
+
+    pub fn parse_voxel(&self, text: &str) -> Result<Voxel, VoxelNameError> {
+        self.resolve(&text.parse()?)
+    }
+
+    pub fn collides(&self, voxel: Voxel) -> bool {
+        self.definition(voxel).is_some_and(|definition| definition.collision)
+    }
+
+    pub fn light_opacity(&self, voxel: Voxel) -> u8 {
+        self.definition(voxel).map_or(0, |definition| definition.opacity)
+    }
+
+    pub fn light_emission(&self, voxel: Voxel) -> u8 {
+        self.definition(voxel).map_or(0, |definition| definition.emission)
+    }
+
+    pub fn is_fluid(&self, voxel: Voxel) -> bool {
+        self.definition(voxel).is_some_and(|definition| definition.fluid)
+    }
+}
+
+/// the definitions shipped with the game, used when the definitions file can't be loaded
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        Self::parse(BUILTIN_DEFINITIONS).expect("builtin block definitions are valid")
+    }
+}
+
+#[cfg(test)]
+pub fn builtin_voxel(name: &str) -> Voxel {
+    BlockRegistry::default().voxel(name).expect("builtin block exists")
+}
+
+#[cfg(test)]
+mod block_registry {
+    use super::*;
+
+    #[test]
+    fn builtin_definitions_load() {
+        let registry = BlockRegistry::default();
+        assert!(registry.voxels().count() == 9);
+        assert!(matches!(registry.voxel("stone"), Some(Voxel::Full(_))));
+        assert!(matches!(registry.voxel("water"), Some(Voxel::Semi(_))));
+        assert!(!registry.collides(builtin_voxel("water")));
+        assert!(registry.name(Voxel::Empty) == "air");
+    }
+
+    #[test]
+    fn every_block_name_round_trips() {
+        let registry = BlockRegistry::default();
+        for voxel in registry.voxels().chain([Voxel::Empty]) {
+            let text = registry.voxel_name(voxel).to_string();
+            assert!(registry.parse_voxel(&text) == Ok(voxel));
+        }
+        assert!(registry.voxel_name(Voxel::Empty).to_string() == "air");
+        assert!(registry.voxel_name(builtin_voxel("stone")).to_string() == "full:stone");
+        assert!(registry.voxel_name(builtin_voxel("water")).to_string() == "semi:water");
+    }
+
+    #[test]
+    fn malformed_names_rejected() {
+        let registry = BlockRegistry::default();
+        assert!("".parse::<VoxelName>() == Err(VoxelNameError::Empty));
+        assert!(matches!("stone".parse::<VoxelName>(), Err(VoxelNameError::MissingClass(_))));
+        assert!(matches!("solid:stone".parse::<VoxelName>(), Err(VoxelNameError::UnknownClass(_))));
+        assert!(matches!("full:Stone".parse::<VoxelName>(), Err(VoxelNameError::InvalidName(_))));
+        assert!(matches!(registry.parse_voxel("full:marble"), Err(VoxelNameError::UnknownBlock(_))));
+        assert!(matches!(registry.parse_voxel("full:water"), Err(VoxelNameError::WrongClass { .. })));
+    }
+
+    #[test]
+    fn duplicate_names_rejected() {
+        let block = "(name: \"rock\", textures: (top: (1, 1), bottom: (1, 1), side: (1, 1)), class: Full, \
+                     collision: true, opacity: 15)";
+        let source = format!("[{}, {}]", block, block);
+        assert!(matches!(BlockRegistry::parse(&source), Err(RegistryError::Invalid { .. })));
+    }
+
+    #[test]
+    fn textures_must_fit_the_atlas() {
+        let source = "[(name: \"rock\", textures: (top: (99, 1), bottom: (1, 1), side: (1, 1)), class: Full, \
+                      collision: true, opacity: 15)]";
+        assert!(matches!(BlockRegistry::parse(source), Err(RegistryError::Invalid { .. })));
+    }
 }
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use noise::NoiseFn;
+use noise::Perlin;
+
+use crate::settings::CaveSettings;
+
+/// hollows caves out of the ground with 3d noise in world coordinates, so a cave running over a chunk border
+/// continues on the other side however the chunks are generated
+#[derive(Clone)]
+pub struct Caves {
+    cavern: Perlin,
+    tunnel: [Perlin; 2],
+    settings: CaveSettings,
+}
+
+impl Default for Caves {
+    fn default() -> Self {
+        Self::new(0, CaveSettings::default())
+    }
+}
+
+impl Caves {
+    pub fn new(seed: u32, settings: CaveSettings) -> Self {
+        Self {
+            cavern: Perlin::new(seed.wrapping_add(3)),
+            tunnel: [Perlin::new(seed.wrapping_add(4)), Perlin::new(seed.wrapping_add(5))],
+            settings,
+        }
+    }
+
+    /// whether the voxel is cave rather than rock, surface is the height of its column, caverns are where
+    /// one noise field peaks and tunnels follow the lines where two others both pass through zero
+    pub fn carved(&self, position: IVec3, surface: i32) -> bool {
+        if position.y > surface - self.settings.min_depth as i32 {
+            return false;
+        }
+
+        let [x, y, z] = position.as_dvec3().to_array();
+        let cavern_threshold = 0.7 - 0.5 * self.settings.cavern_density as f64;
+        if self.settings.cavern_density > 0.
+            && self.cavern.get([x / 48., y / 32., z / 48.]) > cavern_threshold
+        {
+            return true;
+        }
+
+        let tunnel_width = 0.08 * self.settings.tunnel_density as f64;
+        self.tunnel.iter().all(|noise| noise.get([x / 40., y / 30., z / 40.]).abs() < tunnel_width)
+    }
+}
+
+#[cfg(test)]
+mod cave_carving {
+    use super::*;
+
+    fn carved(caves: &Caves, surface: i32) -> Vec<IVec3> {
+        let mut carved = Vec::new();
+        for x in (-64..64).step_by(2) {
+            for y in -64..surface {
+                for z in (-64..64).step_by(2) {
+                    let position = IVec3::new(x, y, z);
+                    if caves.carved(position, surface) {
+                        carved.push(position);
+                    }
+                }
+            }
+        }
+        carved
+    }
+
+    #[test]
+    fn default_density_carves_some() {
+        let caves = Caves::new(2293, CaveSettings::default());
+        let carved = carved(&caves, 16);
+        assert!(!carved.is_empty());
+        assert!(carved.len() < 64 * 80 * 64 / 4);
+    }
+
+    #[test]
+    fn zero_density_carves_nothing() {
+        let caves =
+            Caves::new(2293, CaveSettings { cavern_density: 0., tunnel_density: 0., ..Default::default() });
+        assert!(carved(&caves, 16).is_empty());
+    }
+
+    #[test]
+    fn surface_kept_intact() {
+        let settings = CaveSettings { cavern_density: 1., tunnel_density: 1., min_depth: 5 };
+        let caves = Caves::new(7, settings);
+        assert!(carved(&caves, 16).iter().all(|position| position.y <= 11));
+    }
+
+    #[test]
+    fn same_seed_same_caves() {
+        let settings = CaveSettings::default();
+        let first = carved(&Caves::new(2293, settings.clone()), 0);
+        assert!(first == carved(&Caves::new(2293, settings.clone()), 0));
+        assert!(first != carved(&Caves::new(2294, settings), 0));
+    }
+}
@@ -1,7 +1,11 @@
+use std::iter;
+
+use bevy::math::IVec3;
+
 use crate::block::Voxel;
 use crate::config::blocks::CHUNK_SIZE;
 
-trait _Chunked {
+pub trait Chunked {
     type Output;
 
     const X: usize;
@@ -28,32 +32,491 @@ trait _Chunked {
         (x, y, z)
     }
 
-    fn get(x: usize, y: usize, z: usize) -> Self::Output;
+    fn get(&self, x: usize, y: usize, z: usize) -> Self::Output;
+
+    fn set(&mut self, x: usize, y: usize, z: usize, value: Self::Output);
+}
+
+/// palette indices packed into words, widths are powers of two so an index never straddles two words
+#[derive(Clone)]
+struct PackedIndices {
+    bits: usize,
+    words: Vec<u64>,
+}
+
+impl PackedIndices {
+    fn new(bits: usize, len: usize) -> Self {
+        Self { bits, words: vec![0; len.div_ceil(u64::BITS as usize / bits)] }
+    }
+
+    /// smallest width able to address a palette of `len` entries
+    fn bits_for(len: usize) -> usize {
+        let bits = usize::BITS - len.saturating_sub(1).leading_zeros();
+        (bits as usize).max(1).next_power_of_two()
+    }
+
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let per_word = u64::BITS as usize / self.bits;
+        (index / per_word, index % per_word * self.bits)
+    }
+
+    fn get(&self, index: usize) -> usize {
+        let (word, shift) = self.locate(index);
+        let mask = (1 << self.bits) - 1;
+
+        ((self.words[word] >> shift) & mask) as usize
+    }
+
+    fn set(&mut self, index: usize, value: usize) {
+        let (word, shift) = self.locate(index);
+        let mask = ((1 << self.bits) - 1) << shift;
+
+        self.words[word] = (self.words[word] & !mask) | ((value as u64) << shift & mask);
+    }
+
+    fn widened(&self, bits: usize, len: usize) -> Self {
+        let mut widened = Self::new(bits, len);
+        for index in 0..len {
+            widened.set(index, self.get(index));
+        }
+
+        widened
+    }
+}
+
+#[derive(Clone)]
+enum Storage {
+    /// every voxel is the same, the common case for air and deep stone
+    Uniform(Voxel),
+    Paletted {
+        palette: Vec<Voxel>,
+        indices: PackedIndices,
+    },
+}
+
+pub const MAX_LIGHT: u8 = 15;
+
+/// light is stored as one byte per voxel, sky light in the high nibble and block light in the low one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    Sky,
+    Block,
+}
+
+impl LightChannel {
+    pub const ALL: [Self; 2] = [Self::Sky, Self::Block];
+
+    const fn shift(&self) -> u8 {
+        match self {
+            | Self::Sky => 4,
+            | Self::Block => 0,
+        }
+    }
+
+    pub const fn unpack(&self, packed: u8) -> u8 {
+        (packed >> self.shift()) & MAX_LIGHT
+    }
+
+    fn pack(&self, packed: u8, level: u8) -> u8 {
+        (packed & !(MAX_LIGHT << self.shift())) | (level.min(MAX_LIGHT) << self.shift())
+    }
+}
+
+/// fluid voxels at this level are sources, higher levels are flowing fluid that thins out with distance
+pub const SOURCE_FLOW: u8 = 0;
+pub const MAX_FLOW: u8 = 7;
+
+/// a byte per voxel, for light and fluid flow
+#[derive(Clone)]
+enum ByteStorage {
+    /// unlit chunks underground, fully sunlit ones above the terrain and chunks without flowing fluid never
+    /// allocate
+    Uniform(u8),
+    Varied(Box<[u8]>),
 }
 
+impl ByteStorage {
+    fn get(&self, index: usize) -> u8 {
+        match self {
+            | Self::Uniform(value) => *value,
+            | Self::Varied(values) => values[index],
+        }
+    }
+
+    fn set(&mut self, index: usize, value: u8, size: usize) {
+        if self.get(index) == value {
+            return;
+        }
+        if let Self::Uniform(uniform) = *self {
+            *self = Self::Varied(vec![uniform; size].into_boxed_slice());
+        }
+        if let Self::Varied(values) = self {
+            values[index] = value;
+        }
+    }
+
+    fn compact(&mut self) {
+        if let Self::Varied(values) = self
+            && values.iter().all(|&value| value == values[0])
+        {
+            *self = Self::Uniform(values[0]);
+        }
+    }
+
+    fn allocated(&self) -> usize {
+        match self {
+            | Self::Uniform(_) => 0,
+            | Self::Varied(values) => values.len(),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Chunk {
-    pub voxels: [[[Voxel; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+    storage: Storage,
+    light: ByteStorage,
+    flow: ByteStorage,
 }
 
 impl Chunk {
-    pub fn get(&self, x: usize, y: usize, z: usize, dx: isize, dy: isize, dz: isize) -> Voxel {
-        let [nx, ny, nz] = [
-            (x as isize + dx) as usize,
-            (y as isize + dy) as usize,
-            (z as isize + dz) as usize,
-        ];
+    pub const fn uniform(voxel: Voxel) -> Self {
+        Self {
+            storage: Storage::Uniform(voxel),
+            light: ByteStorage::Uniform(0),
+            flow: ByteStorage::Uniform(SOURCE_FLOW),
+        }
+    }
+
+    /// both channels packed into one byte, see [`LightChannel`]
+    pub fn packed_light(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.light.get(Self::linearize(x, y, z))
+    }
+
+    pub fn light(&self, channel: LightChannel, x: usize, y: usize, z: usize) -> u8 {
+        channel.unpack(self.packed_light(x, y, z))
+    }
 
-        if !(nx < CHUNK_SIZE && ny < CHUNK_SIZE && nz < CHUNK_SIZE) {
-            return Voxel::Empty;
+    pub fn set_light(&mut self, channel: LightChannel, x: usize, y: usize, z: usize, level: u8) {
+        let packed = channel.pack(self.packed_light(x, y, z), level);
+        self.light.set(Self::linearize(x, y, z), packed, Self::size());
+    }
+
+    pub fn clear_light(&mut self) {
+        self.light = ByteStorage::Uniform(0);
+    }
+
+    /// falls back to uniform light storage when every voxel ended up with the same light
+    pub fn compact_light(&mut self) {
+        self.light.compact();
+    }
+
+    /// flow level of a fluid voxel, [`SOURCE_FLOW`] for anything else
+    pub fn flow(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.flow.get(Self::linearize(x, y, z))
+    }
+
+    /// only meaningful on fluid voxels, setting the voxel itself resets its flow to a source
+    pub fn set_flow(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        self.flow.set(Self::linearize(x, y, z), level.min(MAX_FLOW), Self::size());
+    }
+
+    /// every distinct voxel in the chunk, without walking the voxels themselves
+    pub fn kinds(&self) -> impl Iterator<Item = Voxel> + '_ {
+        let palette = match &self.storage {
+            | Storage::Uniform(voxel) => std::slice::from_ref(voxel),
+            | Storage::Paletted { palette, .. } => palette.as_slice(),
+        };
+
+        palette.iter().copied()
+    }
+
+    /// voxels in linear order, x first then y then z
+    pub fn iter(&self) -> impl Iterator<Item = Voxel> + '_ {
+        (0..Self::size()).map(|index| {
+            let (x, y, z) = Self::delinearize(index);
+            self.get(x, y, z)
+        })
+    }
+
+    /// flow levels in the same order as [`Chunk::iter`]
+    pub fn flows(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..Self::size()).map(|index| self.flow.get(index))
+    }
+
+    /// drops palette entries no voxel refers to anymore, voxels and flow both fall back to uniform storage
+    /// when one value is left
+    pub fn compact(&mut self) {
+        if let Storage::Paletted { .. } = self.storage {
+            self.storage = self.iter().collect::<Self>().storage;
+        }
+        self.flow.compact();
+    }
+
+    /// bytes held by this chunk, including its heap allocations
+    pub fn memory_usage(&self) -> usize {
+        let voxels = match &self.storage {
+            | Storage::Uniform(_) => 0,
+            | Storage::Paletted { palette, indices } => {
+                palette.capacity() * size_of::<Voxel>() + indices.words.capacity() * size_of::<u64>()
+            }
+        };
+        size_of::<Self>() + voxels + self.light.allocated() + self.flow.allocated()
+    }
+}
+
+impl Chunked for Chunk {
+    type Output = Voxel;
+
+    const X: usize = CHUNK_SIZE;
+    const Y: usize = CHUNK_SIZE;
+    const Z: usize = CHUNK_SIZE;
+
+    fn get(&self, x: usize, y: usize, z: usize) -> Voxel {
+        match &self.storage {
+            | Storage::Uniform(voxel) => *voxel,
+            | Storage::Paletted { palette, indices } => palette[indices.get(Self::linearize(x, y, z))],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, z: usize, voxel: Voxel) {
+        let index = Self::linearize(x, y, z);
+        self.flow.set(index, SOURCE_FLOW, Self::size());
+        match &mut self.storage {
+            | Storage::Uniform(current) if *current == voxel => {}
+            | Storage::Uniform(current) => {
+                let mut indices = PackedIndices::new(1, Self::size());
+                indices.set(index, 1);
+                self.storage = Storage::Paletted { palette: vec![*current, voxel], indices };
+            }
+            | Storage::Paletted { palette, indices } => {
+                let entry = match palette.iter().position(|&entry| entry == voxel) {
+                    | Some(entry) => entry,
+                    | None => {
+                        palette.push(voxel);
+                        let bits = PackedIndices::bits_for(palette.len());
+                        if bits != indices.bits {
+                            *indices = indices.widened(bits, Self::size());
+                        }
+                        palette.len() - 1
+                    }
+                };
+                indices.set(index, entry);
+            }
         }
-        self.voxels[nz][ny][nx]
     }
 }
 
 impl Default for Chunk {
     fn default() -> Self {
-        Self {
-            voxels: [[[Voxel::Empty; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+        Self::uniform(Voxel::Empty)
+    }
+}
+
+/// chunks compare by their voxels and flow, light is derived from them and the surrounding world
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+            && (0..Self::size()).all(|index| self.flow.get(index) == other.flow.get(index))
+    }
+}
+
+/// builds a chunk from voxels in linear order, missing voxels are empty and extra ones are ignored
+impl FromIterator<Voxel> for Chunk {
+    fn from_iter<T: IntoIterator<Item = Voxel>>(iter: T) -> Self {
+        let mut voxels = iter.into_iter().chain(iter::repeat(Voxel::Empty));
+        let mut chunk = Self::uniform(voxels.next().unwrap_or(Voxel::Empty));
+        for (index, voxel) in voxels.take(Self::size() - 1).enumerate() {
+            let (x, y, z) = Self::delinearize(index + 1);
+            chunk.set(x, y, z, voxel);
+        }
+
+        chunk
+    }
+}
+
+/// offsets to a chunk itself and the 26 chunks sharing a face, an edge or a corner with it
+fn chunk_offsets() -> impl Iterator<Item = IVec3> {
+    (0..27).map(|index| IVec3::new(index % 3, index / 3 % 3, index / 9) - IVec3::ONE)
+}
+
+/// the chunk holding a voxel plus the neighbors across every face, edge and corner of the chunk the voxel
+/// sits on, these are the chunks whose meshes can see the voxel, either as a face or in their occlusion
+pub fn bordering_chunks(chunk_pos: IVec3, location_in_chunk: IVec3) -> impl Iterator<Item = IVec3> {
+    let sides = location_in_chunk.to_array().map(|local| match local {
+        | 0 => -1,
+        | local if local == CHUNK_SIZE as i32 - 1 => 1,
+        | _ => 0,
+    });
+
+    chunk_offsets()
+        .filter(move |offset| (0..3).all(|axis| offset[axis] == 0 || offset[axis] == sides[axis]))
+        .map(move |offset| chunk_pos + offset)
+}
+
+/// the 26 chunks around a chunk, all of their meshes reach into it through their padding
+pub fn surrounding_chunks(chunk_pos: IVec3) -> impl Iterator<Item = IVec3> {
+    chunk_offsets().filter(|&offset| offset != IVec3::ZERO).map(move |offset| chunk_pos + offset)
+}
+
+const PADDED_SIZE: usize = CHUNK_SIZE + 2;
+
+/// a chunk plus a one voxel border copied from its neighbors, owning its data so meshing never needs the
+/// rest of the world
+pub struct PaddedChunk {
+    voxels: Vec<Voxel>,
+    light: Vec<u8>,
+    flow: Vec<u8>,
+}
+
+impl PaddedChunk {
+    pub fn new<'a>(center: &'a Chunk, neighbor: impl Fn(IVec3) -> Option<&'a Chunk>) -> Self {
+        let mut sources = [[[None; 3]; 3]; 3];
+        for (dz, plane) in sources.iter_mut().enumerate() {
+            for (dy, row) in plane.iter_mut().enumerate() {
+                for (dx, source) in row.iter_mut().enumerate() {
+                    let offset = IVec3::new(dx as i32 - 1, dy as i32 - 1, dz as i32 - 1);
+                    *source = if offset == IVec3::ZERO { Some(center) } else { neighbor(offset) };
+                }
+            }
+        }
+
+        let mut voxels = vec![Voxel::Empty; PADDED_SIZE * PADDED_SIZE * PADDED_SIZE];
+        let mut light = vec![0; PADDED_SIZE * PADDED_SIZE * PADDED_SIZE];
+        let mut flow = vec![SOURCE_FLOW; PADDED_SIZE * PADDED_SIZE * PADDED_SIZE];
+        for pz in 0..PADDED_SIZE {
+            for py in 0..PADDED_SIZE {
+                for px in 0..PADDED_SIZE {
+                    let [x, y, z] = [px, py, pz].map(|value| value as isize - 1);
+                    let [sx, sy, sz] =
+                        [x, y, z].map(|value| (value.div_euclid(CHUNK_SIZE as isize) + 1) as usize);
+                    let [lx, ly, lz] = [x, y, z].map(|value| value.rem_euclid(CHUNK_SIZE as isize) as usize);
+
+                    if let Some(chunk) = sources[sz][sy][sx] {
+                        voxels[Self::index(px, py, pz)] = chunk.get(lx, ly, lz);
+                        light[Self::index(px, py, pz)] = chunk.packed_light(lx, ly, lz);
+                        flow[Self::index(px, py, pz)] = chunk.flow(lx, ly, lz);
+                    }
+                }
+            }
+        }
+
+        Self { voxels, light, flow }
+    }
+
+    const fn index(px: usize, py: usize, pz: usize) -> usize {
+        (pz * PADDED_SIZE + py) * PADDED_SIZE + px
+    }
+
+    fn offset_index(x: usize, y: usize, z: usize, dx: isize, dy: isize, dz: isize) -> usize {
+        let [px, py, pz] = [
+            (x as isize + dx + 1) as usize,
+            (y as isize + dy + 1) as usize,
+            (z as isize + dz + 1) as usize,
+        ];
+
+        Self::index(px, py, pz)
+    }
+
+    /// offsets of one voxel past the chunk edge read from the neighbor border
+    pub fn get(&self, x: usize, y: usize, z: usize, dx: isize, dy: isize, dz: isize) -> Voxel {
+        self.voxels[Self::offset_index(x, y, z, dx, dy, dz)]
+    }
+
+    /// light packed the same way as [`Chunk::packed_light`], unloaded neighbors are dark
+    pub fn packed_light(&self, x: usize, y: usize, z: usize, dx: isize, dy: isize, dz: isize) -> u8 {
+        self.light[Self::offset_index(x, y, z, dx, dy, dz)]
+    }
+
+    pub fn flow(&self, x: usize, y: usize, z: usize, dx: isize, dy: isize, dz: isize) -> u8 {
+        self.flow[Self::offset_index(x, y, z, dx, dy, dz)]
+    }
+}
+
+#[cfg(test)]
+mod palette_storage {
+    use super::*;
+    use crate::block::BlockRegistry;
+    use crate::block::builtin_voxel;
+
+    #[test]
+    fn uniform_chunk_stays_small() {
+        let mut chunk = Chunk::uniform(builtin_voxel("stone"));
+        chunk.set(4, 5, 6, builtin_voxel("stone"));
+        assert!(chunk.memory_usage() == size_of::<Chunk>());
+    }
+
+    #[test]
+    fn palette_grows_and_reads_back() {
+        let blocks: Vec<Voxel> = BlockRegistry::default().voxels().collect();
+        let mut chunk = Chunk::default();
+        for (index, &block) in blocks.iter().enumerate() {
+            chunk.set(index, index, 31 - index, block);
+        }
+
+        for (index, &block) in blocks.iter().enumerate() {
+            assert!(chunk.get(index, index, 31 - index) == block);
+        }
+        assert!(chunk.get(31, 0, 0) == Voxel::Empty);
+    }
+
+    #[test]
+    fn light_channels_are_independent() {
+        let mut chunk = Chunk::default();
+        chunk.set_light(LightChannel::Sky, 3, 4, 5, 12);
+        chunk.set_light(LightChannel::Block, 3, 4, 5, 7);
+        assert!(chunk.light(LightChannel::Sky, 3, 4, 5) == 12);
+        assert!(chunk.light(LightChannel::Block, 3, 4, 5) == 7);
+        assert!(chunk.light(LightChannel::Sky, 4, 4, 5) == 0);
+
+        chunk.set_light(LightChannel::Block, 3, 4, 5, 0);
+        chunk.set_light(LightChannel::Sky, 3, 4, 5, 0);
+        chunk.compact_light();
+        assert!(chunk.memory_usage() == size_of::<Chunk>());
+    }
+
+    #[test]
+    fn setting_voxel_resets_flow() {
+        let water = builtin_voxel("water");
+        let mut chunk = Chunk::default();
+        chunk.set(2, 2, 2, water);
+        chunk.set_flow(2, 2, 2, 5);
+        assert!(chunk.flow(2, 2, 2) == 5);
+        assert!(chunk.flow(2, 3, 2) == SOURCE_FLOW);
+
+        chunk.set(2, 2, 2, Voxel::Empty);
+        chunk.set(2, 2, 2, water);
+        assert!(chunk.flow(2, 2, 2) == SOURCE_FLOW);
+        chunk.compact();
+        assert!(chunk.memory_usage() < size_of::<Chunk>() + Chunk::size());
+    }
+
+    #[test]
+    fn compact_returns_to_uniform() {
+        let mut chunk = Chunk::default();
+        chunk.set(1, 2, 3, builtin_voxel("dirt"));
+        chunk.set(1, 2, 3, Voxel::Empty);
+        chunk.compact();
+        assert!(chunk.memory_usage() == size_of::<Chunk>());
+        assert!(chunk == Chunk::default());
+    }
+
+    #[test]
+    fn corner_voxel_dirties_eight_chunks() {
+        let corner = IVec3::new(0, CHUNK_SIZE as i32 - 1, 0);
+        let chunks: Vec<IVec3> = bordering_chunks(IVec3::ZERO, corner).collect();
+        assert!(chunks.len() == 8);
+        assert!(chunks.contains(&IVec3::ZERO) && chunks.contains(&IVec3::new(-1, 1, -1)));
+
+        let face: Vec<IVec3> = bordering_chunks(IVec3::ZERO, IVec3::new(5, 0, 7)).collect();
+        assert!(face == [IVec3::NEG_Y, IVec3::ZERO]);
+        assert!(surrounding_chunks(IVec3::ONE).count() == 26);
+    }
+
+    #[test]
+    fn linearize_round_trips() {
+        for index in [0, 1, CHUNK_SIZE, CHUNK_SIZE * CHUNK_SIZE + 7, Chunk::size() - 1] {
+            let (x, y, z) = Chunk::delinearize(index);
+            assert!(Chunk::linearize(x, y, z) == index);
         }
     }
 }
@@ -2,10 +2,11 @@
 struct _FooBar;
 
 pub mod world {
-    pub const RENDER_DISTANCE: usize = 0;
+    pub const SEA_LEVEL: i32 = 4;
 }
 
 pub mod blocks {
+    pub const BLOCK_DEFINITIONS: &str = "assets/blocks.ron";
     pub const CHUNK_SIZE: usize = 32;
     pub const VOXEL_SIZE: f32 = 1.;
     pub const TRI_COLLIDER_MESH: bevy_rapier3d::prelude::ComputedColliderShape =
@@ -18,27 +19,23 @@ pub mod aesthetics {
     pub const ATLAS_SIZE: usize = 256;
     pub const TEXTURE_SIZE: usize = 16;
     pub const SKYBOX_SIZE: f32 = 2000.;
-    pub const SUN_COLOR: bevy::color::Color = bevy::color::Color::srgb(1., 0.9, 0.9);
-    pub const AMBIENT_COLOR: bevy::color::Color = bevy::color::Color::srgb(1., 0.75, 0.75);
-    pub const SUN_STRENGTH: f32 = 30000.;
-    pub const AMBIENT_STRENGTH: f32 = 750.;
+    pub const SKY_ZENITH_COLOR: bevy::color::Color = bevy::color::Color::srgb(0.25, 0.5, 0.9);
+    pub const SKY_HORIZON_COLOR: bevy::color::Color = bevy::color::Color::srgb(0.75, 0.85, 0.95);
+    pub const SKY_NIGHT_COLOR: bevy::color::Color = bevy::color::Color::srgb(0.01, 0.015, 0.04);
+    pub const UNDERWATER_FOG_COLOR: bevy::color::Color = bevy::color::Color::srgb(0.1, 0.25, 0.45);
+    pub const UNDERWATER_FOG_DENSITY: f32 = 0.12;
+    pub const DAWN_COLOR: bevy::color::Color = bevy::color::Color::srgb(1., 0.55, 0.3);
+    pub const MOON_COLOR: bevy::color::Color = bevy::color::Color::srgb(0.6, 0.7, 1.);
+    pub const NIGHT_AMBIENT_COLOR: bevy::color::Color = bevy::color::Color::srgb(0.35, 0.4, 0.65);
+    pub const HIGHLIGHT_COLOR: bevy::color::Color = bevy::color::Color::srgb(0.1, 0.1, 0.1);
+    pub const HIGHLIGHT_FACE_COLOR: bevy::color::Color = bevy::color::Color::srgba(1., 1., 1., 0.2);
 }
 
-pub mod keys {
-    pub const RAPIER_RENDER: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyY;
-    pub const CAMERA_CYCLE: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyU;
-    pub const PLAYER_RESET: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyP;
-    pub const WALK_FOR: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyW;
-    pub const WALK_LEF: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyA;
-    pub const WALK_BAC: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyS;
-    pub const WALK_RIG: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyD;
-    pub const WALK_UPW: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyR;
-    pub const WALK_DOW: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyF;
-    pub const JUMP: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::Space;
-    pub const CYCLE_BLOCK_UP: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyR;
-    pub const CYCLE_BLOCK_DOWN: bevy::input::keyboard::KeyCode = bevy::input::keyboard::KeyCode::KeyF;
+pub mod save {
+    pub const WORLD_DIRECTORY: &str = "saves/world";
+    pub const REGION_SIZE: i32 = 4;
 }
 
-pub mod player {
-    pub const BLOCK_REACH: f32 = 7.5;
+pub mod settings {
+    pub const SETTINGS_FILE: &str = "settings.ron";
 }
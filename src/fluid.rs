@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::block::BlockRegistry;
+use crate::block::Voxel;
+use crate::chunk::MAX_FLOW;
+use crate::chunk::SOURCE_FLOW;
+use crate::settings::Settings;
+use crate::world::BlockBreakEvent;
+use crate::world::BlockPlaceEvent;
+use crate::world::WorldChunks;
+
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        let tick = app.world().resource::<Settings>().world.fluid_tick;
+        app.insert_resource(FluidFlow {
+            queue: HashSet::new(),
+            timer: Timer::from_seconds(tick, TimerMode::Repeating),
+        });
+        app.add_systems(Update, fluid_edits_queue);
+        app.add_systems(Update, fluid_flow_tick.after(fluid_edits_queue));
+    }
+}
+
+/// voxels updated in a single tick, whatever is left over waits for the next one
+const FLUID_UPDATES_PER_TICK: usize = 4096;
+
+const NEIGHBORS: [IVec3; 6] = [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y, IVec3::Z, IVec3::NEG_Z];
+const HORIZONTAL: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+/// where a flowing voxel can be fed from
+const FEEDERS: [IVec3; 5] = [IVec3::Y, IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// fluid only moves where something happened, the queue holds the voxels to look at on the next tick
+#[derive(Resource)]
+struct FluidFlow {
+    queue: HashSet<IVec3>,
+    timer: Timer,
+}
+
+/// the level a flowing voxel is fed at, falling fluid is fed from above at the first level and anything
+/// else one level below its lowest neighbor of the same fluid, `None` when nothing feeds it anymore
+fn fed_level(position: IVec3, fluid: Voxel, cell: &impl Fn(IVec3) -> Option<(Voxel, u8)>) -> Option<u8> {
+    if cell(position + IVec3::Y).is_some_and(|(voxel, _)| voxel == fluid) {
+        return Some(1);
+    }
+
+    HORIZONTAL
+        .iter()
+        .filter_map(|&offset| cell(position + offset))
+        .filter(|&(voxel, level)| voxel == fluid && level < MAX_FLOW)
+        .map(|(_, level)| level + 1)
+        .min()
+}
+
+/// what a single fluid voxel does this tick, cell looks up a voxel and its flow level and is `None` for
+/// unloaded voxels, which fluid neither flows into nor gives up its level over, sources stay where they are
+/// and flowing voxels follow their feeder, then the fluid falls into empty space below or spreads sideways
+/// one level weaker until it reaches [`MAX_FLOW`]
+pub fn flow_update(
+    position: IVec3,
+    registry: &BlockRegistry,
+    cell: impl Fn(IVec3) -> Option<(Voxel, u8)>,
+) -> Vec<(IVec3, Voxel, u8)> {
+    let Some((fluid, current)) = cell(position).filter(|&(voxel, _)| registry.is_fluid(voxel))
+    else {
+        return Vec::new();
+    };
+
+    let mut changes = Vec::new();
+    let mut level = current;
+    if current != SOURCE_FLOW {
+        match fed_level(position, fluid, &cell) {
+            | Some(fed) => level = fed,
+            | None if FEEDERS.iter().all(|&offset| cell(position + offset).is_some()) => {
+                return vec![(position, Voxel::Empty, SOURCE_FLOW)];
+            }
+            // the feeder might sit in a chunk that isn't loaded, the voxel keeps its level until it is
+            | None => {}
+        }
+        if level != current {
+            changes.push((position, fluid, level));
+        }
+    }
+
+    match cell(position + IVec3::NEG_Y) {
+        | None => return changes,
+        | Some((Voxel::Empty, _)) => {
+            changes.push((position + IVec3::NEG_Y, fluid, 1));
+            return changes;
+        }
+        // falling onto fluid that is still flowing, it spreads from where it lands instead
+        | Some((voxel, below)) if voxel == fluid && below != SOURCE_FLOW => return changes,
+        | Some(_) => {}
+    }
+
+    if level >= MAX_FLOW {
+        return changes;
+    }
+    for offset in HORIZONTAL {
+        let neighbor = position + offset;
+        match cell(neighbor) {
+            | Some((Voxel::Empty, _)) => changes.push((neighbor, fluid, level + 1)),
+            | Some((voxel, other)) if voxel == fluid && other != SOURCE_FLOW && other > level + 1 => {
+                changes.push((neighbor, fluid, level + 1));
+            }
+            | _ => {}
+        }
+    }
+
+    changes
+}
+
+/// updates up to the budget of queued voxels against the world as it was before the tick, where two of
+/// them write the same voxel fluid wins over draining and the lowest level over the others, every voxel
+/// that changed goes back in the queue with its neighbors
+fn flow_step(
+    queue: &mut HashSet<IVec3>,
+    registry: &BlockRegistry,
+    cell: impl Fn(IVec3) -> Option<(Voxel, u8)>,
+) -> Vec<(IVec3, Voxel, u8)> {
+    let batch: Vec<IVec3> = queue.iter().copied().take(FLUID_UPDATES_PER_TICK).collect();
+    let mut writes: HashMap<IVec3, (Voxel, u8)> = HashMap::new();
+    for position in batch {
+        queue.remove(&position);
+        for (target, voxel, level) in flow_update(position, registry, &cell) {
+            let keep = writes.get(&target).is_some_and(|&(written, written_level)| {
+                voxel == Voxel::Empty || (written != Voxel::Empty && written_level <= level)
+            });
+            if !keep {
+                writes.insert(target, (voxel, level));
+            }
+        }
+    }
+
+    for &position in writes.keys() {
+        queue.insert(position);
+        queue.extend(NEIGHBORS.iter().map(|&offset| position + offset));
+    }
+
+    writes.into_iter().map(|(position, (voxel, level))| (position, voxel, level)).collect()
+}
+
+/// breaking a block can open a way for the fluid next to it and placing one can cut a flow off or be fluid
+/// itself, so both look at the voxel and everything around it
+fn fluid_edits_queue(
+    mut flow: ResMut<FluidFlow>,
+    mut break_event: EventReader<BlockBreakEvent>,
+    mut place_event: EventReader<BlockPlaceEvent>,
+) {
+    let edits =
+        break_event.read().map(|event| event.position).chain(place_event.read().map(|event| event.position));
+    for position in edits {
+        flow.queue.insert(position);
+        flow.queue.extend(NEIGHBORS.iter().map(|&offset| position + offset));
+    }
+}
+
+fn fluid_flow_tick(
+    mut flow: ResMut<FluidFlow>,
+    mut world: ResMut<WorldChunks>,
+    registry: Res<BlockRegistry>,
+    time: Res<Time>,
+) {
+    if !flow.timer.tick(time.delta()).just_finished() || flow.queue.is_empty() {
+        return;
+    }
+
+    let writes = flow_step(&mut flow.queue, &registry, |position| world.fluid_cell(position));
+    if !writes.is_empty() {
+        world.write_fluid(&registry, writes);
+    }
+}
+
+#[cfg(test)]
+mod fluid_flow {
+    use super::*;
+    use crate::block::builtin_voxel;
+
+    /// a stone floor at y 0 with open space above it, walled in by unloaded voxels
+    struct Basin {
+        voxels: HashMap<IVec3, (Voxel, u8)>,
+        queue: HashSet<IVec3>,
+    }
+
+    impl Basin {
+        const EXTENT: i32 = 10;
+        const HEIGHT: i32 = 8;
+
+        fn new() -> Self {
+            let mut basin = Self { voxels: HashMap::new(), queue: HashSet::new() };
+            for x in -Self::EXTENT..=Self::EXTENT {
+                for z in -Self::EXTENT..=Self::EXTENT {
+                    basin.voxels.insert(IVec3::new(x, 0, z), (builtin_voxel("stone"), SOURCE_FLOW));
+                }
+            }
+            basin
+        }
+
+        fn cell(&self, position: IVec3) -> Option<(Voxel, u8)> {
+            let inside = position.x.abs() <= Self::EXTENT
+                && position.z.abs() <= Self::EXTENT
+                && (0..Self::HEIGHT).contains(&position.y);
+            inside.then(|| self.voxels.get(&position).copied().unwrap_or((Voxel::Empty, SOURCE_FLOW)))
+        }
+
+        fn edit(&mut self, position: IVec3, voxel: Voxel) {
+            self.voxels.insert(position, (voxel, SOURCE_FLOW));
+            self.queue.insert(position);
+            self.queue.extend(NEIGHBORS.iter().map(|&offset| position + offset));
+        }
+
+        fn settle(&mut self) {
+            let registry = BlockRegistry::default();
+            for _ in 0..200 {
+                let mut queue = std::mem::take(&mut self.queue);
+                let writes = flow_step(&mut queue, &registry, |position| self.cell(position));
+                self.queue = queue;
+                for (position, voxel, level) in writes {
+                    self.voxels.insert(position, (voxel, level));
+                }
+                if self.queue.is_empty() {
+                    return;
+                }
+            }
+            panic!("fluid never settled");
+        }
+
+        fn water(&self, position: IVec3) -> Option<u8> {
+            self.cell(position).filter(|&(voxel, _)| voxel == builtin_voxel("water")).map(|(_, level)| level)
+        }
+    }
+
+    #[test]
+    fn source_spreads_until_max_flow() {
+        let mut basin = Basin::new();
+        basin.edit(IVec3::new(0, 1, 0), builtin_voxel("water"));
+        basin.settle();
+
+        for x in -Basin::EXTENT..=Basin::EXTENT {
+            for z in -Basin::EXTENT..=Basin::EXTENT {
+                let distance = (x.abs() + z.abs()) as u8;
+                let expected = (distance <= MAX_FLOW).then_some(distance);
+                assert!(basin.water(IVec3::new(x, 1, z)) == expected);
+            }
+        }
+        assert!(basin.water(IVec3::new(0, 2, 0)).is_none());
+    }
+
+    #[test]
+    fn fluid_falls_before_spreading() {
+        let mut basin = Basin::new();
+        basin.edit(IVec3::new(0, 5, 0), builtin_voxel("stone"));
+        basin.edit(IVec3::new(1, 5, 0), builtin_voxel("water"));
+        basin.settle();
+
+        for y in 1..5 {
+            assert!(basin.water(IVec3::new(1, y, 0)) == Some(1));
+        }
+        assert!(basin.water(IVec3::new(2, 4, 0)).is_none());
+        assert!(basin.water(IVec3::new(1 + MAX_FLOW as i32 - 1, 1, 0)) == Some(MAX_FLOW));
+        assert!(basin.water(IVec3::new(1 + MAX_FLOW as i32, 1, 0)).is_none());
+    }
+
+    #[test]
+    fn flowing_fluid_drains_without_source() {
+        let mut basin = Basin::new();
+        let source = IVec3::new(1, 1, -2);
+        basin.edit(source, builtin_voxel("water"));
+        basin.settle();
+        assert!(basin.water(source + IVec3::X * 3) == Some(3));
+
+        basin.edit(source, Voxel::Empty);
+        basin.settle();
+        assert!(basin.voxels.values().all(|&(voxel, _)| voxel != builtin_voxel("water")));
+    }
+
+    #[test]
+    fn only_fluids_flow() {
+        let mut basin = Basin::new();
+        basin.edit(IVec3::new(0, 1, 0), builtin_voxel("sand"));
+        basin.settle();
+        assert!(basin.cell(IVec3::new(1, 1, 0)) == Some((Voxel::Empty, SOURCE_FLOW)));
+    }
+}
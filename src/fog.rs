@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+use crate::block::BlockRegistry;
+use crate::config::aesthetics::SKY_HORIZON_COLOR;
+use crate::config::aesthetics::SKY_NIGHT_COLOR;
+use crate::config::aesthetics::UNDERWATER_FOG_COLOR;
+use crate::config::aesthetics::UNDERWATER_FOG_DENSITY;
+use crate::config::blocks::CHUNK_SIZE;
+use crate::config::blocks::VOXEL_SIZE;
+use crate::player::PlayerCamera;
+use crate::settings::Settings;
+use crate::skybox::WorldClock;
+use crate::world::WorldChunks;
+
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostStartup, fog_setup);
+        app.add_systems(Update, fog_update);
+    }
+}
+
+const WATER: &str = "water";
+
+/// chunks load a whole number of chunks around the one the player stands in, so the unloaded edge is
+/// between the render distance and one chunk further away depending on where in that chunk they stand,
+/// the fog closes at the render distance, the nearest the edge can get, except that with a render distance of
+/// zero that would be on top of the player so it closes halfway across their chunk instead
+fn render_distance_falloff(settings: &Settings) -> FogFalloff {
+    let end = (settings.world.render_distance as f32).max(0.5) * CHUNK_SIZE as f32 * VOXEL_SIZE;
+    FogFalloff::Linear { start: end * settings.graphics.fog_start, end }
+}
+
+fn fog_setup(mut commands: Commands, camera: Single<Entity, With<PlayerCamera>>, settings: Res<Settings>) {
+    commands.entity(*camera).insert(DistanceFog {
+        color: SKY_HORIZON_COLOR,
+        falloff: render_distance_falloff(&settings),
+        ..Default::default()
+    });
+}
+
+/// above water the fog takes the color of the horizon so distant terrain melts into the sky, inside water
+/// it switches to a dense exponential fog
+fn fog_update(
+    camera: Single<(&GlobalTransform, &mut DistanceFog), With<PlayerCamera>>,
+    world: Res<WorldChunks>,
+    registry: Res<BlockRegistry>,
+    clock: Res<WorldClock>,
+    settings: Res<Settings>,
+) {
+    let (transform, mut fog) = camera.into_inner();
+    let position = (transform.translation() / VOXEL_SIZE).floor().as_ivec3();
+    let underwater = registry.voxel(WATER).is_some_and(|water| world.voxel(position) == Some(water));
+
+    if underwater {
+        fog.color = UNDERWATER_FOG_COLOR;
+        fog.falloff = FogFalloff::Exponential { density: UNDERWATER_FOG_DENSITY };
+    }
+    else {
+        fog.color = SKY_NIGHT_COLOR.mix(&SKY_HORIZON_COLOR, clock.daylight());
+        fog.falloff = render_distance_falloff(&settings);
+    }
+}
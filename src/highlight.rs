@@ -0,0 +1,75 @@
+use bevy::pbr::NotShadowCaster;
+use bevy::pbr::NotShadowReceiver;
+use bevy::prelude::*;
+
+use crate::config::aesthetics::HIGHLIGHT_COLOR;
+use crate::config::aesthetics::HIGHLIGHT_FACE_COLOR;
+use crate::config::blocks::VOXEL_SIZE;
+use crate::player::BlockTarget;
+
+pub struct TargetHighlightPlugin;
+
+impl Plugin for TargetHighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, highlight_setup);
+        app.add_systems(PostUpdate, highlight_outline);
+        app.add_systems(PostUpdate, highlight_face.before(TransformSystem::TransformPropagate));
+    }
+}
+
+/// pushes the outline and overlay just off the block surface so they don't fight the chunk mesh for depth
+const SURFACE_OFFSET: f32 = 0.002;
+
+#[derive(Component)]
+struct TargetFace;
+
+fn highlight_setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn(TargetFace)
+        .insert(Mesh3d(meshes.add(Rectangle::new(VOXEL_SIZE, VOXEL_SIZE))))
+        .insert(MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: HIGHLIGHT_FACE_COLOR,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..Default::default()
+        })))
+        .insert(NotShadowCaster)
+        .insert(NotShadowReceiver)
+        .insert(Transform::default())
+        .insert(Visibility::Hidden);
+}
+
+fn highlight_outline(mut gizmos: Gizmos, target: Res<BlockTarget>) {
+    let Some(hit) = target.hit
+    else {
+        return;
+    };
+
+    let center = (hit.position.as_vec3() + 0.5) * VOXEL_SIZE;
+    let size = VOXEL_SIZE + SURFACE_OFFSET * 2.;
+    gizmos.cuboid(Transform::from_translation(center).with_scale(Vec3::splat(size)), HIGHLIGHT_COLOR);
+}
+
+/// the overlay sits on the face a placed block would attach to, the rectangle mesh faces +z so it's turned
+/// to the hit normal
+fn highlight_face(
+    target: Res<BlockTarget>,
+    face: Single<(&mut Transform, &mut Visibility), With<TargetFace>>,
+) {
+    let (mut transform, mut visibility) = face.into_inner();
+    let Some(hit) = target.hit.filter(|hit| hit.normal != IVec3::ZERO)
+    else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let normal = hit.normal.as_vec3();
+    let center = (hit.position.as_vec3() + 0.5 + normal * 0.5) * VOXEL_SIZE;
+    transform.translation = center + normal * SURFACE_OFFSET;
+    transform.rotation = Quat::from_rotation_arc(Vec3::Z, normal);
+    *visibility = Visibility::Visible;
+}
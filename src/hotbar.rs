@@ -0,0 +1,313 @@
+use bevy::input::mouse::AccumulatedMouseScroll;
+use bevy::prelude::*;
+use bevy::window::CursorGrabMode;
+use bevy::window::PrimaryWindow;
+
+use crate::block::BlockRegistry;
+use crate::block::Voxel;
+use crate::config::aesthetics::TEXTURE_SIZE;
+use crate::input::Action;
+use crate::input::Actions;
+
+pub struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hotbar>();
+        app.init_resource::<Inventory>();
+        app.add_systems(Startup, hotbar_setup);
+        app.add_systems(Startup, inventory_setup);
+        app.add_systems(Update, hotbar_select);
+        app.add_systems(Update, inventory_toggle);
+        app.add_systems(Update, inventory_pick.run_if(inventory_open));
+        app.add_systems(Update, hotbar_ui_update.after(hotbar_select).after(inventory_pick));
+    }
+}
+
+pub const HOTBAR_SLOTS: usize = 9;
+
+/// the blocks the player can place, one of them selected at a time, empty slots place nothing
+#[derive(Debug, Default, Resource)]
+pub struct Hotbar {
+    pub slots: [Voxel; HOTBAR_SLOTS],
+    selected: usize,
+}
+
+impl Hotbar {
+    /// the first registered blocks in definition order, for a player without a saved layout
+    pub fn filled(registry: &BlockRegistry) -> Self {
+        let mut slots = [Voxel::Empty; HOTBAR_SLOTS];
+        for (slot, voxel) in slots.iter_mut().zip(registry.voxels()) {
+            *slot = voxel;
+        }
+
+        Self { slots, selected: 0 }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn block(&self) -> Voxel {
+        self.slots[self.selected]
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        self.selected = slot % HOTBAR_SLOTS;
+    }
+
+    /// moves the selection by some number of slots, wrapping around either end
+    pub fn scroll(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR_SLOTS as i32) as usize;
+    }
+}
+
+#[derive(Default, Resource)]
+pub struct Inventory {
+    pub open: bool,
+}
+
+pub fn inventory_open(inventory: Res<Inventory>) -> bool {
+    inventory.open
+}
+
+pub fn inventory_closed(inventory: Res<Inventory>) -> bool {
+    !inventory.open
+}
+
+#[derive(Component)]
+struct HotbarSlot(usize);
+
+#[derive(Component)]
+struct HotbarIcon(usize);
+
+#[derive(Component)]
+struct HotbarLabel;
+
+#[derive(Component)]
+struct InventoryScreen;
+
+#[derive(Component)]
+struct InventoryEntry(Voxel);
+
+const SLOT_SIZE: f32 = 48.;
+const ICON_SIZE: f32 = 36.;
+const SLOT_COLOR: Color = Color::srgba(0., 0., 0., 0.4);
+const SLOT_BORDER: Color = Color::srgb(0.3, 0.3, 0.3);
+const SELECTED_BORDER: Color = Color::WHITE;
+
+/// the atlas tile on the side of the block, in pixels, for cutting its icon out of the atlas
+fn icon_rect(registry: &BlockRegistry, voxel: Voxel) -> Option<Rect> {
+    let tile = registry.definition(voxel)?.textures.side.as_vec2() * TEXTURE_SIZE as f32;
+    Some(Rect::from_corners(tile, tile + TEXTURE_SIZE as f32))
+}
+
+fn block_icon(atlas: &Handle<Image>, registry: &BlockRegistry, voxel: Voxel) -> ImageNode {
+    ImageNode { image: atlas.clone(), rect: icon_rect(registry, voxel), ..Default::default() }
+}
+
+fn hotbar_setup(
+    mut commands: Commands,
+    mut hotbar: ResMut<Hotbar>,
+    registry: Res<BlockRegistry>,
+    asset_server: Res<AssetServer>,
+) {
+    *hotbar = Hotbar::filled(&registry);
+    let atlas = asset_server.load("texture_atlas.png");
+
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            width: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(4.),
+            ..Default::default()
+        })
+        .with_children(|root| {
+            root.spawn(HotbarLabel)
+                .insert(Text::default())
+                .insert(TextFont { font_size: 15., ..Default::default() })
+                .insert(TextColor::BLACK);
+
+            root.spawn(Node { column_gap: Val::Px(4.), ..Default::default() }).with_children(|row| {
+                for slot in 0..HOTBAR_SLOTS {
+                    row.spawn(HotbarSlot(slot))
+                        .insert(Node {
+                            width: Val::Px(SLOT_SIZE),
+                            height: Val::Px(SLOT_SIZE),
+                            border: UiRect::all(Val::Px(3.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        })
+                        .insert(BackgroundColor(SLOT_COLOR))
+                        .insert(BorderColor(SLOT_BORDER))
+                        .with_child((
+                            HotbarIcon(slot),
+                            block_icon(&atlas, &registry, hotbar.slots[slot]),
+                            Node {
+                                width: Val::Px(ICON_SIZE),
+                                height: Val::Px(ICON_SIZE),
+                                ..Default::default()
+                            },
+                        ));
+                }
+            });
+        });
+}
+
+/// every registered block laid out in a grid, clicking one puts it in the selected hotbar slot
+fn inventory_setup(mut commands: Commands, registry: Res<BlockRegistry>, asset_server: Res<AssetServer>) {
+    let atlas = asset_server.load("texture_atlas.png");
+
+    commands
+        .spawn(InventoryScreen)
+        .insert(Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        })
+        .insert(BackgroundColor(Color::srgba(0., 0., 0., 0.5)))
+        .insert(Visibility::Hidden)
+        .with_children(|screen| {
+            screen
+                .spawn(Node {
+                    max_width: Val::Px((SLOT_SIZE + 4.) * HOTBAR_SLOTS as f32),
+                    flex_wrap: FlexWrap::Wrap,
+                    column_gap: Val::Px(4.),
+                    row_gap: Val::Px(4.),
+                    ..Default::default()
+                })
+                .with_children(|grid| {
+                    for voxel in registry.voxels() {
+                        grid.spawn(InventoryEntry(voxel))
+                            .insert(Button)
+                            .insert(Node {
+                                width: Val::Px(SLOT_SIZE),
+                                height: Val::Px(SLOT_SIZE),
+                                border: UiRect::all(Val::Px(3.)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            })
+                            .insert(BackgroundColor(SLOT_COLOR))
+                            .insert(BorderColor(SLOT_BORDER))
+                            .with_child((
+                                block_icon(&atlas, &registry, voxel),
+                                Node {
+                                    width: Val::Px(ICON_SIZE),
+                                    height: Val::Px(ICON_SIZE),
+                                    ..Default::default()
+                                },
+                            ));
+                    }
+                });
+        });
+}
+
+/// number slots jump straight to a slot, the wheel and the cycle actions step through them
+fn hotbar_select(mut hotbar: ResMut<Hotbar>, actions: Res<Actions>, scroll: Res<AccumulatedMouseScroll>) {
+    for slot in 0..HOTBAR_SLOTS {
+        if actions.just_pressed(Action::SelectSlot(slot as u8 + 1)) {
+            hotbar.select(slot);
+        }
+    }
+    if actions.just_pressed(Action::CycleBlockUp) {
+        hotbar.scroll(1);
+    }
+    if actions.just_pressed(Action::CycleBlockDown) {
+        hotbar.scroll(-1);
+    }
+    if scroll.delta.y != 0. {
+        hotbar.scroll(-scroll.delta.y.signum() as i32);
+    }
+}
+
+/// the cursor is freed while the inventory is open so the grid can be clicked
+fn inventory_toggle(
+    mut inventory: ResMut<Inventory>,
+    mut screen: Single<&mut Visibility, With<InventoryScreen>>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+    actions: Res<Actions>,
+) {
+    if !actions.just_pressed(Action::OpenInventory) {
+        return;
+    }
+
+    inventory.open = !inventory.open;
+    **screen = match inventory.open {
+        | true => Visibility::Visible,
+        | false => Visibility::Hidden,
+    };
+    window.cursor_options.visible = inventory.open;
+    window.cursor_options.grab_mode = match inventory.open {
+        | true => CursorGrabMode::None,
+        | false => CursorGrabMode::Locked,
+    };
+}
+
+fn inventory_pick(
+    mut hotbar: ResMut<Hotbar>,
+    entries: Query<(&Interaction, &InventoryEntry), Changed<Interaction>>,
+) {
+    for (interaction, entry) in &entries {
+        if *interaction == Interaction::Pressed {
+            let selected = hotbar.selected;
+            hotbar.slots[selected] = entry.0;
+        }
+    }
+}
+
+fn hotbar_ui_update(
+    hotbar: Res<Hotbar>,
+    registry: Res<BlockRegistry>,
+    mut slots: Query<(&HotbarSlot, &mut BorderColor)>,
+    mut icons: Query<(&HotbarIcon, &mut ImageNode, &mut Visibility)>,
+    mut label: Single<&mut Text, With<HotbarLabel>>,
+) {
+    if !hotbar.is_changed() {
+        return;
+    }
+
+    for (slot, mut border) in &mut slots {
+        border.0 = if slot.0 == hotbar.selected { SELECTED_BORDER } else { SLOT_BORDER };
+    }
+    for (icon, mut image, mut visibility) in &mut icons {
+        let voxel = hotbar.slots[icon.0];
+        image.rect = icon_rect(&registry, voxel);
+        *visibility = match voxel {
+            | Voxel::Empty => Visibility::Hidden,
+            | _ => Visibility::Inherited,
+        };
+    }
+    label.0 = registry.name(hotbar.block()).to_string();
+}
+
+#[cfg(test)]
+mod hotbar_slots {
+    use super::*;
+
+    #[test]
+    fn filled_from_registry_order() {
+        let registry = BlockRegistry::default();
+        let hotbar = Hotbar::filled(&registry);
+        assert!(hotbar.slots.iter().copied().eq(registry.voxels().take(HOTBAR_SLOTS)));
+        assert!(hotbar.block() == registry.voxels().next().unwrap());
+    }
+
+    #[test]
+    fn selection_wraps() {
+        let mut hotbar = Hotbar::default();
+        hotbar.scroll(-1);
+        assert!(hotbar.selected() == HOTBAR_SLOTS - 1);
+        hotbar.scroll(2);
+        assert!(hotbar.selected() == 1);
+        hotbar.select(HOTBAR_SLOTS + 3);
+        assert!(hotbar.selected() == 3);
+    }
+}
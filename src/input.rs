@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::settings::Settings;
+
+pub struct InputActionPlugin;
+
+impl Plugin for InputActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Actions>();
+        app.init_resource::<InputContext>();
+        app.add_systems(Startup, bindings_conflict_check);
+        app.add_systems(PreUpdate, actions_update.after(InputSystem));
+    }
+}
+
+/// everything the player can do, systems ask for actions and never look at keys directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    BreakBlock,
+    PlaceBlock,
+    CycleBlockUp,
+    CycleBlockDown,
+    /// hotbar slots are numbered from 1 on the left
+    SelectSlot(u8),
+    OpenInventory,
+    CameraCycle,
+    PlayerReset,
+    PauseTime,
+    SkipTime,
+    ToggleDebugRender,
+    CycleMesher,
+    ReportMemory,
+    WidenView,
+    NarrowView,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+/// which set of bindings is live, walking with the player body or flying with the detached camera
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Resource)]
+pub enum InputContext {
+    #[default]
+    Walking,
+    Flying,
+}
+
+impl InputContext {
+    pub const ALL: [Self; 2] = [Self::Walking, Self::Flying];
+}
+
+/// global bindings apply in every context, a context binding for the same action replaces the global one
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub global: BTreeMap<Action, Binding>,
+    pub walking: BTreeMap<Action, Binding>,
+    pub flying: BTreeMap<Action, Binding>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Action::*;
+        use Binding::*;
+
+        Self {
+            global: BTreeMap::from([
+                (MoveForward, Key(KeyCode::KeyW)),
+                (MoveBack, Key(KeyCode::KeyS)),
+                (MoveLeft, Key(KeyCode::KeyA)),
+                (MoveRight, Key(KeyCode::KeyD)),
+                (BreakBlock, Mouse(MouseButton::Left)),
+                (PlaceBlock, Mouse(MouseButton::Right)),
+                (CycleBlockUp, Key(KeyCode::KeyR)),
+                (CycleBlockDown, Key(KeyCode::KeyF)),
+                (SelectSlot(1), Key(KeyCode::Digit1)),
+                (SelectSlot(2), Key(KeyCode::Digit2)),
+                (SelectSlot(3), Key(KeyCode::Digit3)),
+                (SelectSlot(4), Key(KeyCode::Digit4)),
+                (SelectSlot(5), Key(KeyCode::Digit5)),
+                (SelectSlot(6), Key(KeyCode::Digit6)),
+                (SelectSlot(7), Key(KeyCode::Digit7)),
+                (SelectSlot(8), Key(KeyCode::Digit8)),
+                (SelectSlot(9), Key(KeyCode::Digit9)),
+                (OpenInventory, Key(KeyCode::KeyE)),
+                (CameraCycle, Key(KeyCode::KeyU)),
+                (PlayerReset, Key(KeyCode::KeyP)),
+                (PauseTime, Key(KeyCode::KeyT)),
+                (SkipTime, Key(KeyCode::KeyG)),
+                (ToggleDebugRender, Key(KeyCode::KeyY)),
+                (CycleMesher, Key(KeyCode::KeyM)),
+                (ReportMemory, Key(KeyCode::KeyN)),
+                (WidenView, Key(KeyCode::Equal)),
+                (NarrowView, Key(KeyCode::Minus)),
+            ]),
+            walking: BTreeMap::from([(Jump, Key(KeyCode::Space))]),
+            flying: BTreeMap::from([(MoveUp, Key(KeyCode::Space)), (MoveDown, Key(KeyCode::ShiftLeft))]),
+        }
+    }
+}
+
+/// two actions in the same context waiting on the same key or button
+#[derive(Debug, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub context: InputContext,
+    pub binding: Binding,
+    pub actions: Vec<Action>,
+}
+
+impl Bindings {
+    fn context(&self, context: InputContext) -> &BTreeMap<Action, Binding> {
+        match context {
+            | InputContext::Walking => &self.walking,
+            | InputContext::Flying => &self.flying,
+        }
+    }
+
+    /// every binding live in the context
+    pub fn active(&self, context: InputContext) -> impl Iterator<Item = (Action, Binding)> + '_ {
+        let specific = self.context(context);
+        self.global
+            .iter()
+            .filter(|(action, _)| !specific.contains_key(action))
+            .chain(specific)
+            .map(|(&action, &binding)| (action, binding))
+    }
+
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        let mut conflicts = Vec::new();
+        for context in InputContext::ALL {
+            let mut by_binding: HashMap<Binding, Vec<Action>> = HashMap::new();
+            for (action, binding) in self.active(context) {
+                by_binding.entry(binding).or_default().push(action);
+            }
+            conflicts.extend(
+                by_binding
+                    .into_iter()
+                    .filter(|(_, actions)| actions.len() > 1)
+                    .map(|(binding, actions)| BindingConflict { context, binding, actions }),
+            );
+        }
+
+        conflicts
+    }
+}
+
+/// the actions held and newly pressed this frame under the current context
+#[derive(Default, Resource)]
+pub struct Actions {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+}
+
+impl Actions {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+fn actions_update(
+    mut actions: ResMut<Actions>,
+    context: Res<InputContext>,
+    settings: Res<Settings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+) {
+    actions.pressed.clear();
+    actions.just_pressed.clear();
+
+    for (action, binding) in settings.bindings.active(*context) {
+        let (pressed, just_pressed) = match binding {
+            | Binding::Key(key) => (keys.pressed(key), keys.just_pressed(key)),
+            | Binding::Mouse(button) => (mouse.pressed(button), mouse.just_pressed(button)),
+        };
+        if pressed {
+            actions.pressed.insert(action);
+        }
+        if just_pressed {
+            actions.just_pressed.insert(action);
+        }
+    }
+}
+
+fn bindings_conflict_check(settings: Res<Settings>) {
+    for conflict in settings.bindings.conflicts() {
+        error!("{:?} is bound to {:?} while {:?}", conflict.binding, conflict.actions, conflict.context);
+    }
+}
+
+#[cfg(test)]
+mod action_bindings {
+    use super::*;
+
+    #[test]
+    fn default_bindings_have_no_conflicts() {
+        assert!(Bindings::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn context_overrides_global() {
+        let mut bindings = Bindings::default();
+        bindings.flying.insert(Action::MoveForward, Binding::Key(KeyCode::ArrowUp));
+
+        let find = |context| bindings.active(context).find(|&(action, _)| action == Action::MoveForward);
+        assert!(find(InputContext::Flying) == Some((Action::MoveForward, Binding::Key(KeyCode::ArrowUp))));
+        assert!(find(InputContext::Walking) == Some((Action::MoveForward, Binding::Key(KeyCode::KeyW))));
+        assert!(
+            bindings
+                .active(InputContext::Flying)
+                .filter(|&(action, _)| action == Action::MoveForward)
+                .count()
+                == 1
+        );
+    }
+
+    #[test]
+    fn conflicts_reported_per_context() {
+        let mut bindings = Bindings::default();
+        bindings.flying.insert(Action::MoveUp, Binding::Key(KeyCode::KeyR));
+
+        let conflicts = bindings.conflicts();
+        assert!(conflicts.len() == 1);
+        assert!(conflicts[0].context == InputContext::Flying);
+        assert!(conflicts[0].actions == [Action::CycleBlockUp, Action::MoveUp]);
+    }
+}
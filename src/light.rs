@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+use bevy::math::IVec3;
+
+use crate::block::BlockRegistry;
+use crate::block::Voxel;
+use crate::chunk::Chunk;
+use crate::chunk::Chunked;
+use crate::chunk::LightChannel;
+use crate::chunk::MAX_LIGHT;
+use crate::chunk::bordering_chunks;
+use crate::config::blocks::CHUNK_SIZE;
+
+const DIRECTIONS: [IVec3; 6] = [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y, IVec3::Z, IVec3::NEG_Z];
+
+/// light left after stepping into a voxel, sky light keeps full strength straight down through clear voxels
+fn attenuate(channel: LightChannel, level: u8, opacity: u8, direction: IVec3) -> u8 {
+    if channel == LightChannel::Sky && direction == IVec3::NEG_Y && level == MAX_LIGHT && opacity == 0 {
+        return MAX_LIGHT;
+    }
+
+    level.saturating_sub(1 + opacity)
+}
+
+fn split(position: IVec3) -> (IVec3, [usize; 3]) {
+    let size = IVec3::splat(CHUNK_SIZE as i32);
+
+    (position.div_euclid(size), position.rem_euclid(size).to_array().map(|value| value as usize))
+}
+
+/// flood fill over the loaded chunks, light never spreads into chunks that aren't loaded and is pulled back
+/// in from the border once they are
+pub struct LightPropagator<'a> {
+    chunks: &'a mut HashMap<IVec3, Chunk>,
+    loaded: &'a HashSet<IVec3>,
+    registry: &'a BlockRegistry,
+    /// the highest layer of chunks, open sky falls into it from above
+    max_chunk_y: i32,
+    increase: VecDeque<(IVec3, LightChannel)>,
+    decrease: VecDeque<(IVec3, LightChannel, u8)>,
+    lit: HashSet<IVec3>,
+    touched: HashSet<IVec3>,
+}
+
+impl<'a> LightPropagator<'a> {
+    pub fn new(
+        chunks: &'a mut HashMap<IVec3, Chunk>,
+        loaded: &'a HashSet<IVec3>,
+        registry: &'a BlockRegistry,
+        max_chunk_y: i32,
+    ) -> Self {
+        Self {
+            chunks,
+            loaded,
+            registry,
+            max_chunk_y,
+            increase: VecDeque::new(),
+            decrease: VecDeque::new(),
+            lit: HashSet::new(),
+            touched: HashSet::new(),
+        }
+    }
+
+    fn chunk(&self, chunk_pos: IVec3) -> Option<&Chunk> {
+        self.loaded.contains(&chunk_pos).then(|| self.chunks.get(&chunk_pos)).flatten()
+    }
+
+    fn voxel(&self, position: IVec3) -> Option<Voxel> {
+        let (chunk_pos, [x, y, z]) = split(position);
+
+        Some(self.chunk(chunk_pos)?.get(x, y, z))
+    }
+
+    fn light(&self, channel: LightChannel, position: IVec3) -> Option<u8> {
+        let (chunk_pos, [x, y, z]) = split(position);
+
+        Some(self.chunk(chunk_pos)?.light(channel, x, y, z))
+    }
+
+    fn set_light(&mut self, channel: LightChannel, position: IVec3, level: u8) {
+        let (chunk_pos, [x, y, z]) = split(position);
+        let Some(chunk) = self.chunks.get_mut(&chunk_pos)
+        else {
+            return;
+        };
+
+        chunk.set_light(channel, x, y, z, level);
+        self.lit.insert(chunk_pos);
+        self.touched.extend(bordering_chunks(chunk_pos, IVec3::new(x as i32, y as i32, z as i32)));
+    }
+
+    /// lights a freshly loaded chunk, sky light falls in from the chunk above or from the top of the world
+    /// and the light of loaded neighbors flows in across the borders
+    pub fn seed_chunk(&mut self, chunk_pos: IVec3) {
+        let sky_above = match self.chunk(chunk_pos + IVec3::Y) {
+            | Some(above) => sky_below(above),
+            | None if chunk_pos.y >= self.max_chunk_y => OPEN_SKY,
+            | None => [[0; CHUNK_SIZE]; CHUNK_SIZE],
+        };
+
+        self.seed_interior(chunk_pos, &sky_above);
+        self.seed_borders(chunk_pos);
+    }
+
+    /// lights a chunk from the inside, sky light falls in through the top at the levels given and every
+    /// voxel gives off what its block emits
+    fn seed_interior(&mut self, chunk_pos: IVec3, sky_above: &SkyRow) {
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+        let Some(chunk) = self.chunks.get_mut(&chunk_pos)
+        else {
+            return;
+        };
+        chunk.clear_light();
+
+        // whole columns of sky light are filled in directly, only voxels that can still brighten a
+        // neighbor go through the queue
+        for (z, row) in sky_above.iter().enumerate() {
+            for (x, &sky) in row.iter().enumerate() {
+                let mut level = sky;
+                for y in (0..CHUNK_SIZE).rev() {
+                    let opacity = self.registry.light_opacity(chunk.get(x, y, z));
+                    level = attenuate(LightChannel::Sky, level, opacity, IVec3::NEG_Y);
+                    if level == 0 {
+                        break;
+                    }
+                    chunk.set_light(LightChannel::Sky, x, y, z, level);
+                }
+            }
+        }
+
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let level = chunk.light(LightChannel::Sky, x, y, z);
+                    if level <= 1 {
+                        continue;
+                    }
+
+                    let border = [x, y, z].iter().any(|&value| value == 0 || value == CHUNK_SIZE - 1);
+                    if border
+                        || [[x + 1, z], [x - 1, z], [x, z + 1], [x, z - 1]].into_iter().any(|[nx, nz]| {
+                            self.registry.light_opacity(chunk.get(nx, y, nz)) < MAX_LIGHT
+                                && chunk.light(LightChannel::Sky, nx, y, nz) < level - 1
+                        })
+                    {
+                        self.increase.push_back((
+                            origin + IVec3::new(x as i32, y as i32, z as i32),
+                            LightChannel::Sky,
+                        ));
+                    }
+                }
+            }
+        }
+
+        if chunk.kinds().any(|voxel| self.registry.light_emission(voxel) > 0) {
+            for index in 0..Chunk::size() {
+                let (x, y, z) = Chunk::delinearize(index);
+                let emission = self.registry.light_emission(chunk.get(x, y, z));
+                if emission > 0 {
+                    chunk.set_light(LightChannel::Block, x, y, z, emission);
+                    self.increase
+                        .push_back((origin + IVec3::new(x as i32, y as i32, z as i32), LightChannel::Block));
+                }
+            }
+        }
+
+        self.lit.insert(chunk_pos);
+    }
+
+    /// lets light flow both ways across the faces a chunk shares with loaded neighbors, which is all that is
+    /// left to do for a chunk lit on its own by [`light_chunk`]
+    pub fn seed_borders(&mut self, chunk_pos: IVec3) {
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+        for direction in DIRECTIONS {
+            if self.chunk(chunk_pos + direction).is_none() {
+                continue;
+            }
+
+            let axis =
+                direction.to_array().iter().position(|&value| value != 0).expect("directions are unit axes");
+            let [u_axis, v_axis] = [(axis + 1) % 3, (axis + 2) % 3];
+            for u in 0..CHUNK_SIZE as i32 {
+                for v in 0..CHUNK_SIZE as i32 {
+                    let mut local = IVec3::ZERO;
+                    local[axis] = if direction[axis] > 0 { CHUNK_SIZE as i32 - 1 } else { 0 };
+                    local[u_axis] = u;
+                    local[v_axis] = v;
+
+                    let inside = origin + local;
+                    for position in [inside, inside + direction] {
+                        for channel in LightChannel::ALL {
+                            if self.light(channel, position).is_some_and(|level| level > 1) {
+                                self.increase.push_back((position, channel));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// clears the light that passed through a voxel that just changed and lets its surroundings fill the
+    /// space back in
+    pub fn voxel_changed(&mut self, position: IVec3) {
+        for channel in LightChannel::ALL {
+            let Some(level) = self.light(channel, position)
+            else {
+                return;
+            };
+            self.set_light(channel, position, 0);
+            self.decrease.push_back((position, channel, level));
+        }
+
+        // nothing stores the sky above the top of the world, so it is poured back in by hand
+        let (chunk_pos, [_, y, _]) = split(position);
+        if chunk_pos.y >= self.max_chunk_y
+            && y == CHUNK_SIZE - 1
+            && self.chunk(chunk_pos + IVec3::Y).is_none()
+            && let Some(voxel) = self.voxel(position)
+        {
+            let level = attenuate(LightChannel::Sky, MAX_LIGHT, self.registry.light_opacity(voxel), IVec3::NEG_Y);
+            if level > 0 {
+                self.set_light(LightChannel::Sky, position, level);
+                self.increase.push_back((position, LightChannel::Sky));
+            }
+        }
+
+        if let Some(emission) = self.voxel(position).map(|voxel| self.registry.light_emission(voxel))
+            && emission > 0
+        {
+            self.set_light(LightChannel::Block, position, emission);
+            self.increase.push_back((position, LightChannel::Block));
+        }
+    }
+
+    /// picks light back up from positions left over by [`LightPropagator::propagate_within`]
+    pub fn resume(&mut self, queued: VecDeque<(IVec3, LightChannel)>) {
+        self.increase.extend(queued);
+    }
+
+    /// runs the queued changes to completion, returning every chunk whose mesh can see changed light
+    pub fn propagate(self) -> HashSet<IVec3> {
+        self.propagate_within(usize::MAX).0
+    }
+
+    /// clears removed light completely but spreads light at most the budget of steps, returning the chunks
+    /// whose mesh can see changed light along with the positions still waiting to spread
+    pub fn propagate_within(mut self, budget: usize) -> (HashSet<IVec3>, VecDeque<(IVec3, LightChannel)>) {
+        while let Some((position, channel, level)) = self.decrease.pop_front() {
+            for direction in DIRECTIONS {
+                let neighbor = position + direction;
+                let Some(neighbor_level) = self.light(channel, neighbor)
+                else {
+                    continue;
+                };
+                if neighbor_level == 0 {
+                    continue;
+                }
+
+                let sky_column =
+                    channel == LightChannel::Sky && direction == IVec3::NEG_Y && level == MAX_LIGHT;
+                if neighbor_level < level || sky_column {
+                    self.set_light(channel, neighbor, 0);
+                    self.decrease.push_back((neighbor, channel, neighbor_level));
+
+                    let emission = self.voxel(neighbor).map_or(0, |voxel| self.registry.light_emission(voxel));
+                    if channel == LightChannel::Block && emission > 0 {
+                        self.set_light(channel, neighbor, emission);
+                        self.increase.push_back((neighbor, channel));
+                    }
+                }
+                else {
+                    self.increase.push_back((neighbor, channel));
+                }
+            }
+        }
+
+        for _ in 0..budget {
+            let Some((position, channel)) = self.increase.pop_front()
+            else {
+                break;
+            };
+            let Some(level) = self.light(channel, position)
+            else {
+                continue;
+            };
+
+            for direction in DIRECTIONS {
+                let neighbor = position + direction;
+                let Some(voxel) = self.voxel(neighbor)
+                else {
+                    continue;
+                };
+
+                let spread = attenuate(channel, level, self.registry.light_opacity(voxel), direction);
+                if self.light(channel, neighbor).is_some_and(|current| spread > current) {
+                    self.set_light(channel, neighbor, spread);
+                    self.increase.push_back((neighbor, channel));
+                }
+            }
+        }
+
+        for chunk_pos in &self.lit {
+            if let Some(chunk) = self.chunks.get_mut(chunk_pos) {
+                chunk.compact_light();
+            }
+        }
+
+        (self.touched, self.increase)
+    }
+}
+
+/// sky light along one layer of a chunk, indexed by z then x
+pub type SkyRow = [[u8; CHUNK_SIZE]; CHUNK_SIZE];
+
+/// what falls into the top of the world
+pub const OPEN_SKY: SkyRow = [[MAX_LIGHT; CHUNK_SIZE]; CHUNK_SIZE];
+
+/// the sky light in the bottom layer of a chunk, which is what falls into the chunk below it
+pub fn sky_below(chunk: &Chunk) -> SkyRow {
+    let mut row = [[0; CHUNK_SIZE]; CHUNK_SIZE];
+    for (z, line) in row.iter_mut().enumerate() {
+        for (x, level) in line.iter_mut().enumerate() {
+            *level = chunk.light(LightChannel::Sky, x, 0, z);
+        }
+    }
+
+    row
+}
+
+/// lights a chunk on its own before it joins the world, so the flood fill can run off the main thread, sky
+/// light falls in from the row above and only the light crossing into neighbors is left for
+/// [`LightPropagator::seed_borders`]
+pub fn light_chunk(chunk_pos: IVec3, chunk: Chunk, sky_above: &SkyRow, registry: &BlockRegistry) -> Chunk {
+    let mut chunks = HashMap::from([(chunk_pos, chunk)]);
+    let loaded = HashSet::from([chunk_pos]);
+    // alone the chunk is the top of its own world, though seeding from the inside never looks above it
+    let mut propagator = LightPropagator::new(&mut chunks, &loaded, registry, chunk_pos.y);
+    propagator.seed_interior(chunk_pos, sky_above);
+    propagator.propagate();
+
+    chunks.remove(&chunk_pos).expect("the chunk was just lit")
+}
+
+#[cfg(test)]
+mod propagation {
+    use super::*;
+    use crate::block::builtin_voxel;
+
+    const TOP: IVec3 = IVec3::new(0, 3, 0);
+
+    fn light_world(chunks: &mut HashMap<IVec3, Chunk>) -> HashSet<IVec3> {
+        let registry = BlockRegistry::default();
+        let loaded: HashSet<IVec3> = chunks.keys().copied().collect();
+        let mut order: Vec<IVec3> = loaded.iter().copied().collect();
+        order.sort_by_key(|chunk_pos| -chunk_pos.y);
+
+        for chunk_pos in order {
+            let mut propagator = LightPropagator::new(chunks, &loaded, &registry, TOP.y);
+            propagator.seed_chunk(chunk_pos);
+            propagator.propagate();
+        }
+
+        loaded
+    }
+
+    fn roofed_chunk() -> Chunk {
+        let mut chunk = Chunk::default();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.set(x, CHUNK_SIZE - 1, z, builtin_voxel("stone"));
+            }
+        }
+
+        chunk
+    }
+
+    #[test]
+    fn open_sky_is_fully_lit() {
+        let mut chunks = HashMap::from([(TOP, Chunk::default())]);
+        light_world(&mut chunks);
+
+        let chunk = &chunks[&TOP];
+        assert!(chunk.light(LightChannel::Sky, 0, 0, 0) == MAX_LIGHT);
+        assert!(chunk.light(LightChannel::Sky, 17, 9, 31) == MAX_LIGHT);
+        assert!(chunk.memory_usage() < 1024);
+    }
+
+    #[test]
+    fn light_falls_off_under_a_roof() {
+        let mut roofed = roofed_chunk();
+        roofed.set(5, CHUNK_SIZE - 1, 5, Voxel::Empty);
+        let mut chunks = HashMap::from([(TOP, roofed)]);
+        light_world(&mut chunks);
+
+        let chunk = &chunks[&TOP];
+        assert!(chunk.light(LightChannel::Sky, 5, 0, 5) == MAX_LIGHT);
+        assert!(chunk.light(LightChannel::Sky, 6, 0, 5) == MAX_LIGHT - 1);
+        assert!(chunk.light(LightChannel::Sky, 8, 0, 7) == MAX_LIGHT - 5);
+        assert!(chunk.light(LightChannel::Sky, 30, 0, 30) == 0);
+    }
+
+    #[test]
+    fn light_crosses_chunk_borders() {
+        let mut chunks = HashMap::from([(TOP, Chunk::default()), (TOP + IVec3::X, roofed_chunk())]);
+        light_world(&mut chunks);
+
+        let covered = &chunks[&(TOP + IVec3::X)];
+        assert!(covered.light(LightChannel::Sky, 0, 10, 10) == MAX_LIGHT - 1);
+        assert!(covered.light(LightChannel::Sky, 3, 10, 10) == MAX_LIGHT - 4);
+    }
+
+    #[test]
+    fn lit_alone_then_bordered_matches_lit_in_place() {
+        let registry = BlockRegistry::default();
+        let mut roofed = roofed_chunk();
+        roofed.set(5, CHUNK_SIZE - 1, 5, Voxel::Empty);
+        let positions = [TOP, TOP + IVec3::X, TOP + IVec3::NEG_Y];
+
+        let mut in_place: HashMap<IVec3, Chunk> =
+            positions.iter().map(|&chunk_pos| (chunk_pos, roofed.clone())).collect();
+        light_world(&mut in_place);
+
+        let mut alone = HashMap::new();
+        let mut loaded = HashSet::new();
+        for chunk_pos in positions {
+            let sky_above = alone.get(&(chunk_pos + IVec3::Y)).map_or(OPEN_SKY, sky_below);
+            alone.insert(chunk_pos, light_chunk(chunk_pos, roofed.clone(), &sky_above, &registry));
+            loaded.insert(chunk_pos);
+            let mut propagator = LightPropagator::new(&mut alone, &loaded, &registry, TOP.y);
+            propagator.seed_borders(chunk_pos);
+            propagator.propagate();
+        }
+
+        for chunk_pos in positions {
+            for index in 0..Chunk::size() {
+                let (x, y, z) = Chunk::delinearize(index);
+                for channel in LightChannel::ALL {
+                    assert!(
+                        alone[&chunk_pos].light(channel, x, y, z)
+                            == in_place[&chunk_pos].light(channel, x, y, z)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn placing_and_breaking_relights() {
+        let registry = BlockRegistry::default();
+        let mut chunks = HashMap::from([(TOP, Chunk::default())]);
+        let loaded = light_world(&mut chunks);
+
+        let position = IVec3::new(5, CHUNK_SIZE as i32 - 1, 5) + TOP * CHUNK_SIZE as i32;
+        chunks.get_mut(&TOP).unwrap().set(5, CHUNK_SIZE - 1, 5, builtin_voxel("stone"));
+        let mut propagator = LightPropagator::new(&mut chunks, &loaded, &registry, TOP.y);
+        propagator.voxel_changed(position);
+        let touched = propagator.propagate();
+        assert!(touched.contains(&TOP));
+        assert!(chunks[&TOP].light(LightChannel::Sky, 5, CHUNK_SIZE - 1, 5) == 0);
+        assert!(chunks[&TOP].light(LightChannel::Sky, 5, 20, 5) == MAX_LIGHT - 1);
+
+        chunks.get_mut(&TOP).unwrap().set(5, CHUNK_SIZE - 1, 5, Voxel::Empty);
+        let mut propagator = LightPropagator::new(&mut chunks, &loaded, &registry, TOP.y);
+        propagator.voxel_changed(position);
+        propagator.propagate();
+        assert!(chunks[&TOP].light(LightChannel::Sky, 5, 20, 5) == MAX_LIGHT);
+    }
+}
@@ -1,9 +1,23 @@
+mod biome;
 mod block;
+mod cave;
 mod chunk;
 mod config;
+mod fluid;
+mod fog;
+mod highlight;
+mod hotbar;
+mod input;
+mod light;
+mod material;
 mod mesher;
+mod ore;
 mod player;
+mod raycast;
+mod save;
+mod settings;
 mod skybox;
+mod tree;
 mod voxels;
 mod world;
 
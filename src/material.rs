@@ -0,0 +1,83 @@
+use bevy::pbr::ExtendedMaterial;
+use bevy::pbr::MaterialExtension;
+use bevy::pbr::MaterialPipeline;
+use bevy::pbr::MaterialPipelineKey;
+use bevy::prelude::*;
+use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::AsBindGroup;
+use bevy::render::render_resource::RenderPipelineDescriptor;
+use bevy::render::render_resource::ShaderRef;
+use bevy::render::render_resource::SpecializedMeshPipelineError;
+
+use crate::config::aesthetics::ATLAS_SIZE;
+use crate::config::aesthetics::SKY_HORIZON_COLOR;
+use crate::config::aesthetics::SKY_NIGHT_COLOR;
+use crate::config::aesthetics::SKY_ZENITH_COLOR;
+use crate::config::aesthetics::TEXTURE_SIZE;
+
+pub type AtlasMaterial = ExtendedMaterial<StandardMaterial, AtlasTiling>;
+
+/// wraps the tile-unit uvs from the mesher back into a single atlas tile, so merged quads repeat their
+/// texture instead of stretching it across the atlas
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct AtlasTiling {
+    #[uniform(100)]
+    pub tile_step: f32,
+}
+
+impl Default for AtlasTiling {
+    fn default() -> Self {
+        Self { tile_step: TEXTURE_SIZE as f32 / ATLAS_SIZE as f32 }
+    }
+}
+
+impl MaterialExtension for AtlasTiling {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/atlas_tiling.wgsl".into()
+    }
+}
+
+/// gradient sky drawn on the inside of a sphere around the camera, the sun direction and color are copied
+/// from the scene's sun every frame
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct SkyMaterial {
+    #[uniform(0)]
+    pub sun_direction: Vec3,
+    #[uniform(1)]
+    pub sun_color: LinearRgba,
+    #[uniform(2)]
+    pub zenith_color: LinearRgba,
+    #[uniform(3)]
+    pub horizon_color: LinearRgba,
+    #[uniform(4)]
+    pub night_color: LinearRgba,
+}
+
+impl Default for SkyMaterial {
+    fn default() -> Self {
+        Self {
+            sun_direction: Vec3::Y,
+            sun_color: LinearRgba::WHITE,
+            zenith_color: SKY_ZENITH_COLOR.into(),
+            horizon_color: SKY_HORIZON_COLOR.into(),
+            night_color: SKY_NIGHT_COLOR.into(),
+        }
+    }
+}
+
+impl Material for SkyMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/sky.wgsl".into()
+    }
+
+    /// the camera sits inside the sphere, so the faces it sees are the back faces
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
@@ -1,12 +1,17 @@
 use bevy::asset::RenderAssetUsages;
 use bevy::math::Vec2;
 use bevy::math::Vec3;
+use bevy::prelude::Resource;
 use bevy::render::mesh::Indices;
 use bevy::render::mesh::Mesh;
 use bevy::render::mesh::PrimitiveTopology;
 
+use crate::block::BlockRegistry;
 use crate::block::Voxel;
-use crate::chunk::Chunk;
+use crate::chunk::LightChannel;
+use crate::chunk::MAX_FLOW;
+use crate::chunk::MAX_LIGHT;
+use crate::chunk::PaddedChunk;
 use crate::config::aesthetics::ATLAS_SIZE;
 use crate::config::aesthetics::TEXTURE_SIZE;
 use crate::config::blocks::CHUNK_SIZE;
@@ -23,6 +28,8 @@ enum VoxelFace {
 }
 
 impl VoxelFace {
+    const ALL: [Self; 6] = [Self::Top, Self::Bot, Self::Rig, Self::Lef, Self::Fro, Self::Bac];
+
     #[rustfmt::skip]
     const fn normal(&self) -> Vec3 {
         match self {
@@ -34,141 +41,353 @@ impl VoxelFace {
             | Self::Bac => Vec3::new(0. , 0. , -1.),
         }
     }
+
+    #[rustfmt::skip]
+    const fn offset(&self) -> [isize; 3] {
+        match self {
+            | Self::Top => [0 , 1 , 0 ],
+            | Self::Bot => [0 , -1, 0 ],
+            | Self::Rig => [1 , 0 , 0 ],
+            | Self::Lef => [-1, 0 , 0 ],
+            | Self::Fro => [0 , 0 , 1 ],
+            | Self::Bac => [0 , 0 , -1],
+        }
+    }
+
+    /// corners of the face on a unit voxel, opposite corners are the first and last
+    const fn corners(&self) -> [[usize; 3]; 4] {
+        match self {
+            | Self::Top => [[0, 1, 0], [1, 1, 0], [0, 1, 1], [1, 1, 1]],
+            | Self::Bot => [[0, 0, 0], [1, 0, 0], [0, 0, 1], [1, 0, 1]],
+            | Self::Rig => [[1, 0, 0], [1, 1, 0], [1, 0, 1], [1, 1, 1]],
+            | Self::Lef => [[0, 0, 0], [0, 1, 0], [0, 0, 1], [0, 1, 1]],
+            | Self::Fro => [[0, 0, 1], [0, 1, 1], [1, 0, 1], [1, 1, 1]],
+            | Self::Bac => [[0, 0, 0], [0, 1, 0], [1, 0, 0], [1, 1, 0]],
+        }
+    }
+
+    /// axis indices into `[x, y, z]` as `[normal, u, v]`, where u and v are the texture axes of the face
+    const fn axes(&self) -> [usize; 3] {
+        match self {
+            | Self::Top | Self::Bot => [1, 0, 2],
+            | Self::Rig | Self::Lef => [0, 2, 1],
+            | Self::Fro | Self::Bac => [2, 0, 1],
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Resource)]
+pub enum MeshingMode {
+    Culled,
+    #[default]
+    Greedy,
 }
 
 #[derive(Debug)]
 pub struct Quad {
     vox_loc: [usize; 3],
+    size: [usize; 3],
     face: VoxelFace,
     block: Voxel,
+    /// light of the voxel the face looks into, packed as in [`crate::chunk::Chunk::packed_light`]
+    light: u8,
+    /// occlusion per corner from 0 for a fully enclosed corner to 3 for an open one
+    occlusion: [u8; 4],
+    /// flow level the top edge is lowered by, only ever set on the surface of flowing fluid
+    drop: u8,
+    /// flow level the bottom edge is raised by, only ever set on the side of fluid standing above a lower
+    /// surface of the same fluid, where the face is just the strip between the two
+    rise: u8,
 }
 
 impl Quad {
+    /// the quad is split along the diagonal whose corners are least occluded, otherwise the shading
+    /// interpolates differently depending on which way the quad happened to be cut
     const fn indices(&self, start: u32) -> [u32; 6] {
+        let [a, b, c, d] = self.occlusion;
+        if a + d > b + c {
+            return [start, start + 2, start + 3, start, start + 3, start + 1];
+        }
+
         [start, start + 2, start + 1, start + 1, start + 2, start + 3]
     }
 
+    /// uvs in tile units, these run past 1 on merged quads and are wrapped back into the atlas tile by the
+    /// material so the texture repeats once per voxel
     fn texture_uvs(&self) -> [Vec2; 4] {
+        let [.., u_axis, v_axis] = self.face.axes();
+        let [u, v] = [self.size[u_axis] as f32, self.size[v_axis] as f32];
+
+        match self.face {
+            | VoxelFace::Top | VoxelFace::Bot => {
+                [Vec2::new(0., 0.), Vec2::new(u, 0.), Vec2::new(0., v), Vec2::new(u, v)]
+            }
+            | _ => [Vec2::new(u, v), Vec2::new(u, 0.), Vec2::new(0., v), Vec2::new(0., 0.)],
+        }
+    }
+
+    fn atlas_uvs(&self, registry: &BlockRegistry) -> [Vec2; 4] {
         const STEP: f32 = TEXTURE_SIZE as f32 / ATLAS_SIZE as f32;
 
-        let atlas_offset = match self.block {
-            | Voxel::Full(block) => block.texture_offset(),
-            | Voxel::Semi(block) => block.texture_offset(),
-            | Voxel::Empty => unreachable!("empty blocks should never proceed in mesher"),
+        let Some(definition) = registry.definition(self.block)
+        else {
+            unreachable!("empty blocks should never proceed in mesher");
         };
 
-        match self.face {
-            | VoxelFace::Top => [
-                (Vec2::new(0., 0.) + atlas_offset.top.as_vec2()) * STEP,
-                (Vec2::new(1., 0.) + atlas_offset.top.as_vec2()) * STEP,
-                (Vec2::new(0., 1.) + atlas_offset.top.as_vec2()) * STEP,
-                (Vec2::new(1., 1.) + atlas_offset.top.as_vec2()) * STEP,
-            ],
-            | VoxelFace::Bot => [
-                (Vec2::new(0., 0.) + atlas_offset.bot.as_vec2()) * STEP,
-                (Vec2::new(1., 0.) + atlas_offset.bot.as_vec2()) * STEP,
-                (Vec2::new(0., 1.) + atlas_offset.bot.as_vec2()) * STEP,
-                (Vec2::new(1., 1.) + atlas_offset.bot.as_vec2()) * STEP,
-            ],
-            | _ => [
-                (Vec2::new(1., 1.) + atlas_offset.sid.as_vec2()) * STEP,
-                (Vec2::new(1., 0.) + atlas_offset.sid.as_vec2()) * STEP,
-                (Vec2::new(0., 1.) + atlas_offset.sid.as_vec2()) * STEP,
-                (Vec2::new(0., 0.) + atlas_offset.sid.as_vec2()) * STEP,
-            ],
-        }
+        let tile = match self.face {
+            | VoxelFace::Top => definition.textures.top,
+            | VoxelFace::Bot => definition.textures.bottom,
+            | _ => definition.textures.side,
+        };
+
+        [tile.as_vec2() * STEP; 4]
     }
 
     fn positions(&self, voxel_size: f32) -> [Vec3; 4] {
         let [x, y, z] = self.vox_loc.map(|value| value as f32);
-        let positions = match self.face {
-            | VoxelFace::Top => [[0, 1, 0], [1, 1, 0], [0, 1, 1], [1, 1, 1]],
-            | VoxelFace::Bot => [[0, 0, 0], [1, 0, 0], [0, 0, 1], [1, 0, 1]],
-            | VoxelFace::Rig => [[1, 0, 0], [1, 1, 0], [1, 0, 1], [1, 1, 1]],
-            | VoxelFace::Lef => [[0, 0, 0], [0, 1, 0], [0, 0, 1], [0, 1, 1]],
-            | VoxelFace::Fro => [[0, 0, 1], [0, 1, 1], [1, 0, 1], [1, 1, 1]],
-            | VoxelFace::Bac => [[0, 0, 0], [0, 1, 0], [1, 0, 0], [1, 1, 0]],
-        };
-
-        [
-            Vec3::new(
-                (x + positions[0][0] as f32) * voxel_size,
-                (y + positions[0][1] as f32) * voxel_size,
-                (z + positions[0][2] as f32) * voxel_size,
-            ),
-            Vec3::new(
-                (x + positions[1][0] as f32) * voxel_size,
-                (y + positions[1][1] as f32) * voxel_size,
-                (z + positions[1][2] as f32) * voxel_size,
-            ),
+        let [w, h, d] = self.size.map(|value| value as f32);
+        let drop = self.drop as f32 / (MAX_FLOW + 1) as f32;
+        let rise = self.rise as f32 / (MAX_FLOW + 1) as f32;
+        self.face.corners().map(|[px, py, pz]| {
             Vec3::new(
-                (x + positions[2][0] as f32) * voxel_size,
-                (y + positions[2][1] as f32) * voxel_size,
-                (z + positions[2][2] as f32) * voxel_size,
-            ),
-            Vec3::new(
-                (x + positions[3][0] as f32) * voxel_size,
-                (y + positions[3][1] as f32) * voxel_size,
-                (z + positions[3][2] as f32) * voxel_size,
-            ),
-        ]
+                (x + px as f32 * w) * voxel_size,
+                (y + rise + py as f32 * (h - drop - rise)) * voxel_size,
+                (z + pz as f32 * d) * voxel_size,
+            )
+        })
     }
 
     fn normals(&self) -> [Vec3; 4] {
         [self.face.normal(); 4]
     }
+
+    /// sky light in red, block light in green and corner occlusion in blue, the material turns these into
+    /// brightness
+    fn colors(&self) -> [[f32; 4]; 4] {
+        let [sky, block] =
+            LightChannel::ALL.map(|channel| channel.unpack(self.light) as f32 / MAX_LIGHT as f32);
+
+        self.occlusion.map(|occlusion| [sky, block, occlusion as f32 / 3., 1.])
+    }
 }
 
-pub enum _Visibility {
+/// which of the two meshes a voxel's faces go into, semi blocks are drawn in the transparent one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshPass {
     Transparent,
     Opaque,
 }
 
-impl _Visibility {
-    fn _matches_voxel(&self, voxel: Voxel) -> bool {
+impl MeshPass {
+    fn matches_voxel(&self, voxel: Voxel) -> bool {
         match self {
-            | Self::Transparent => match voxel {
-                | Voxel::Semi(_) => true,
-                | _ => false,
-            },
-            | Self::Opaque => match voxel {
-                | Voxel::Full(_) => true,
-                | _ => false,
-            },
+            | Self::Transparent => matches!(voxel, Voxel::Semi(_)),
+            | Self::Opaque => matches!(voxel, Voxel::Full(_)),
         }
     }
 }
 
-pub fn generate_opaque_mesh(chunk: &Chunk) -> Vec<Quad> {
+/// classic voxel corner occlusion, every corner looks at the two edge neighbors and the diagonal neighbor in
+/// the layer the face points into, two solid edges close the corner off regardless of the diagonal
+fn corner_occlusion(chunk: &PaddedChunk, [x, y, z]: [usize; 3], face: VoxelFace) -> [u8; 4] {
+    let [_, u_axis, v_axis] = face.axes();
+    let normal = face.offset();
+
+    face.corners().map(|corner| {
+        let [mut side_u, mut side_v] = [normal; 2];
+        side_u[u_axis] = if corner[u_axis] == 1 { 1 } else { -1 };
+        side_v[v_axis] = if corner[v_axis] == 1 { 1 } else { -1 };
+        let mut diagonal = side_u;
+        diagonal[v_axis] = side_v[v_axis];
+
+        let [u, v, d] = [side_u, side_v, diagonal]
+            .map(|[dx, dy, dz]| matches!(chunk.get(x, y, z, dx, dy, dz), Voxel::Full(_)) as u8);
+        if u == 1 && v == 1 { 0 } else { 3 - u - v - d }
+    })
+}
+
+/// a fluid voxel with the same fluid above is full to the top, otherwise its surface sits lower the further
+/// the fluid has flowed, offset picks a horizontal neighbor of the voxel instead
+fn surface_drop(chunk: &PaddedChunk, [x, y, z]: [usize; 3], [dx, dz]: [isize; 2], current: Voxel) -> u8 {
+    if chunk.get(x, y, z, dx, 1, dz) == current { 0 } else { chunk.flow(x, y, z, dx, 0, dz) }
+}
+
+fn face_visible(current: Voxel, neighbor: Voxel) -> bool {
+    if let Voxel::Full(_) = neighbor {
+        return false;
+    }
+
+    neighbor != current
+}
+
+/// whether a face is drawn, as the flow level its bottom edge is raised by, the side of a fluid facing the
+/// same fluid is hidden unless that neighbor's surface sits lower, then the step between the two surfaces is
+/// left open
+fn face_rise(
+    chunk: &PaddedChunk,
+    location: [usize; 3],
+    face: VoxelFace,
+    current: Voxel,
+    drop: u8,
+) -> Option<u8> {
+    let [x, y, z] = location;
+    let [dx, dy, dz] = face.offset();
+    let neighbor = chunk.get(x, y, z, dx, dy, dz);
+    if neighbor != current || dy != 0 {
+        return face_visible(current, neighbor).then_some(0);
+    }
+
+    let neighbor_drop = surface_drop(chunk, location, [dx, dz], current);
+    (neighbor_drop > drop).then_some(MAX_FLOW + 1 - neighbor_drop)
+}
+
+pub struct ChunkMesh {
+    pub opaque: Vec<Quad>,
+    pub transparent: Vec<Quad>,
+}
+
+impl ChunkMesh {
+    pub fn vertex_count(&self) -> usize {
+        (self.opaque.len() + self.transparent.len()) * 4
+    }
+
+    /// water is the only semi block the player can move through
+    pub fn collision_quads<'a>(&'a self, registry: &'a BlockRegistry) -> impl Iterator<Item = &'a Quad> {
+        self.opaque.iter().chain(self.transparent.iter().filter(|quad| registry.collides(quad.block)))
+    }
+}
+
+/// transparent faces are drawn additively, so the order they end up in the mesh doesn't change the result
+pub fn generate_mesh(chunk: &PaddedChunk, mode: MeshingMode) -> ChunkMesh {
+    let generate = match mode {
+        | MeshingMode::Culled => generate_culled_mesh,
+        | MeshingMode::Greedy => generate_greedy_mesh,
+    };
+
+    ChunkMesh {
+        opaque: generate(chunk, MeshPass::Opaque),
+        transparent: generate(chunk, MeshPass::Transparent),
+    }
+}
+
+pub fn generate_culled_mesh(chunk: &PaddedChunk, pass: MeshPass) -> Vec<Quad> {
     let mut output = Vec::new();
 
     for z in 0..CHUNK_SIZE {
         for y in 0..CHUNK_SIZE {
             for x in 0..CHUNK_SIZE {
-                let current = chunk.voxels[z][y][x];
-                if current == Voxel::Empty {
+                let current = chunk.get(x, y, z, 0, 0, 0);
+                if !pass.matches_voxel(current) {
                     continue;
                 }
 
-                #[rustfmt::skip]
-                let neighbors = [
-                    (VoxelFace::Top, chunk.get(x, y, z, 0 , 1 , 0 )),
-                    (VoxelFace::Bot, chunk.get(x, y, z, 0 , -1, 0 )),
-                    (VoxelFace::Rig, chunk.get(x, y, z, 1 , 0 , 0 )),
-                    (VoxelFace::Lef, chunk.get(x, y, z, -1, 0 , 0 )),
-                    (VoxelFace::Fro, chunk.get(x, y, z, 0 , 0 , 1 )),
-                    (VoxelFace::Bac, chunk.get(x, y, z, 0 , 0 , -1)),
-                ];
-
-                for (direction, neighbor) in neighbors {
-                    if let Voxel::Full(_) = neighbor {
+                let drop = surface_drop(chunk, [x, y, z], [0, 0], current);
+                for direction in VoxelFace::ALL {
+                    let [dx, dy, dz] = direction.offset();
+                    let Some(rise) = face_rise(chunk, [x, y, z], direction, current, drop)
+                    else {
+                        continue;
+                    };
+
+                    output.push(Quad {
+                        vox_loc: [x, y, z],
+                        size: [1, 1, 1],
+                        face: direction,
+                        block: current,
+                        light: chunk.packed_light(x, y, z, dx, dy, dz),
+                        occlusion: corner_occlusion(chunk, [x, y, z], direction),
+                        drop,
+                        rise,
+                    });
+                }
+            }
+        }
+    }
+
+    output
+}
+
+pub fn generate_greedy_mesh(chunk: &PaddedChunk, pass: MeshPass) -> Vec<Quad> {
+    let mut output = Vec::new();
+
+    for direction in VoxelFace::ALL {
+        let [normal_axis, u_axis, v_axis] = direction.axes();
+        let [dx, dy, dz] = direction.offset();
+
+        for layer in 0..CHUNK_SIZE {
+            let mut mask = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+            for (v, row) in mask.iter_mut().enumerate() {
+                for (u, cell) in row.iter_mut().enumerate() {
+                    let mut location = [0; 3];
+                    location[normal_axis] = layer;
+                    location[u_axis] = u;
+                    location[v_axis] = v;
+
+                    let [x, y, z] = location;
+                    let current = chunk.get(x, y, z, 0, 0, 0);
+                    if !pass.matches_voxel(current) {
                         continue;
                     }
-                    else if neighbor == current {
+                    let drop = surface_drop(chunk, location, [0, 0], current);
+                    if let Some(rise) = face_rise(chunk, location, direction, current, drop) {
+                        let light = chunk.packed_light(x, y, z, dx, dy, dz);
+                        let occlusion = corner_occlusion(chunk, location, direction);
+                        *cell = Some((current, light, occlusion, drop, rise));
+                    }
+                }
+            }
+
+            for v in 0..CHUNK_SIZE {
+                let mut u = 0;
+                while u < CHUNK_SIZE {
+                    let Some(cell) = mask[v][u]
+                    else {
+                        u += 1;
                         continue;
+                    };
+
+                    let mut width = 1;
+                    while u + width < CHUNK_SIZE && mask[v][u + width] == Some(cell) {
+                        width += 1;
+                    }
+
+                    // a lowered top or raised bottom only fits a single voxel, so those sides never stack
+                    let (.., drop, rise) = cell;
+                    let stacks = v_axis != 1 || (drop == 0 && rise == 0);
+                    let mut height = 1;
+                    while stacks
+                        && v + height < CHUNK_SIZE
+                        && mask[v + height][u..u + width].iter().all(|&other| other == Some(cell))
+                    {
+                        height += 1;
                     }
 
-                    output.push(Quad { vox_loc: [x, y, z], face: direction, block: current });
+                    for row in &mut mask[v..v + height] {
+                        row[u..u + width].fill(None);
+                    }
+
+                    let mut location = [0; 3];
+                    location[normal_axis] = layer;
+                    location[u_axis] = u;
+                    location[v_axis] = v;
+
+                    let mut size = [1; 3];
+                    size[u_axis] = width;
+                    size[v_axis] = height;
+
+                    let (block, light, occlusion, ..) = cell;
+                    output.push(Quad {
+                        vox_loc: location,
+                        size,
+                        face: direction,
+                        block,
+                        light,
+                        occlusion,
+                        drop,
+                        rise,
+                    });
+
+                    u += width;
                 }
             }
         }
@@ -177,18 +396,22 @@ pub fn generate_opaque_mesh(chunk: &Chunk) -> Vec<Quad> {
     output
 }
 
-pub fn build_mesh(mesh: &[Quad]) -> Mesh {
+pub fn build_mesh<'a>(mesh: impl IntoIterator<Item = &'a Quad>, registry: &BlockRegistry) -> Mesh {
     let mut pos = Vec::new();
     let mut nor = Vec::new();
     let mut uvs = Vec::new();
+    let mut atl = Vec::new();
+    let mut col = Vec::new();
     let mut ind = Vec::new();
 
-    for face in mesh.iter() {
+    for face in mesh {
         let offset = pos.len() as u32;
         ind.extend_from_slice(&face.indices(offset));
         pos.extend_from_slice(&face.positions(VOXEL_SIZE));
         nor.extend_from_slice(&face.normals());
         uvs.extend_from_slice(&face.texture_uvs());
+        atl.extend_from_slice(&face.atlas_uvs(registry));
+        col.extend_from_slice(&face.colors());
     }
 
     let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
@@ -196,8 +419,293 @@ pub fn build_mesh(mesh: &[Quad]) -> Mesh {
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, pos);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, nor);
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_1, atl);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, col);
 
     mesh.generate_tangents().expect("failed to autogen mesh tangents");
 
     mesh
 }
+
+#[cfg(test)]
+mod greedy_mesher {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::block::builtin_voxel;
+    use crate::chunk::Chunk;
+    use crate::chunk::Chunked;
+
+    fn unit_faces(quads: &[Quad]) -> BTreeSet<([usize; 3], VoxelFace, Voxel)> {
+        let mut faces = BTreeSet::new();
+        for quad in quads {
+            let [x, y, z] = quad.vox_loc;
+            let [w, h, d] = quad.size;
+            for dz in 0..d {
+                for dy in 0..h {
+                    for dx in 0..w {
+                        assert!(faces.insert(([x + dx, y + dy, z + dz], quad.face, quad.block)));
+                    }
+                }
+            }
+        }
+
+        faces
+    }
+
+    #[test]
+    fn flat_layer_merges() {
+        let mut chunk = Chunk::default();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.set(x, 0, z, builtin_voxel("grass"));
+            }
+        }
+
+        let padded = PaddedChunk::new(&chunk, |_| None);
+        let culled = generate_culled_mesh(&padded, MeshPass::Opaque);
+        let greedy = generate_greedy_mesh(&padded, MeshPass::Opaque);
+        assert!(culled.len() == CHUNK_SIZE * CHUNK_SIZE * 2 + CHUNK_SIZE * 4);
+        assert!(greedy.len() == 6);
+    }
+
+    #[test]
+    fn differently_lit_faces_stay_separate() {
+        let mut chunk = Chunk::default();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.set(x, 0, z, builtin_voxel("grass"));
+                chunk.set_light(LightChannel::Sky, x, 1, z, if x < 4 { MAX_LIGHT } else { 3 });
+            }
+        }
+
+        let padded = PaddedChunk::new(&chunk, |_| None);
+        let greedy = generate_greedy_mesh(&padded, MeshPass::Opaque);
+        let tops: Vec<&Quad> = greedy.iter().filter(|quad| quad.face == VoxelFace::Top).collect();
+        assert!(tops.len() == 2);
+        assert!(tops.iter().any(|quad| quad.size[0] == 4 && quad.colors()[0][0] == 1.));
+    }
+
+    #[test]
+    fn different_blocks_stay_separate() {
+        let mut chunk = Chunk::default();
+        chunk.set(0, 0, 0, builtin_voxel("grass"));
+        chunk.set(1, 0, 0, builtin_voxel("stone"));
+
+        let greedy = generate_greedy_mesh(&PaddedChunk::new(&chunk, |_| None), MeshPass::Opaque);
+        let tops: Vec<_> = greedy.iter().filter(|quad| quad.face == VoxelFace::Top).collect();
+        assert!(tops.len() == 2);
+        assert!(tops.iter().all(|quad| quad.size == [1, 1, 1]));
+    }
+
+    #[test]
+    fn matches_culled_faces() {
+        let [grass, water, coal, stone] = ["grass", "water", "coal", "stone"].map(builtin_voxel);
+        let mut chunk = Chunk::default();
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let height = (x * 3 + z * 5) % 17 + 8;
+                    let voxel = match y {
+                        | _ if y > height => Voxel::Empty,
+                        | _ if y == height => grass,
+                        | _ if (x + z) % 9 == 0 => water,
+                        | _ if (x * y + z) % 13 == 0 => coal,
+                        | _ => stone,
+                    };
+                    chunk.set(x, y, z, voxel);
+                }
+            }
+        }
+
+        let padded = PaddedChunk::new(&chunk, |_| None);
+        for pass in [MeshPass::Opaque, MeshPass::Transparent] {
+            let culled = generate_culled_mesh(&padded, pass);
+            let greedy = generate_greedy_mesh(&padded, pass);
+            assert!(greedy.len() < culled.len());
+            assert!(unit_faces(&culled) == unit_faces(&greedy));
+        }
+    }
+}
+
+#[cfg(test)]
+mod neighbor_culling {
+    use bevy::math::IVec3;
+
+    use super::*;
+    use crate::block::builtin_voxel;
+    use crate::chunk::Chunk;
+    use crate::chunk::Chunked;
+
+    fn solid_chunk() -> Chunk {
+        Chunk::uniform(builtin_voxel("stone"))
+    }
+
+    #[test]
+    fn border_faces_culled_by_neighbor() {
+        let center = solid_chunk();
+        let neighbor = solid_chunk();
+        let padded = PaddedChunk::new(&center, |offset| (offset == IVec3::X).then_some(&neighbor));
+
+        let quads = generate_culled_mesh(&padded, MeshPass::Opaque);
+        assert!(quads.iter().all(|quad| quad.face != VoxelFace::Rig));
+        assert!(quads.iter().filter(|quad| quad.face == VoxelFace::Lef).count() == CHUNK_SIZE * CHUNK_SIZE);
+    }
+
+    #[test]
+    fn border_faces_kept_against_air() {
+        let center = solid_chunk();
+        let neighbor = Chunk::default();
+        let padded = PaddedChunk::new(&center, |offset| (offset == IVec3::NEG_Y).then_some(&neighbor));
+
+        let quads = generate_greedy_mesh(&padded, MeshPass::Opaque);
+        assert!(quads.len() == 6);
+    }
+
+    #[test]
+    fn water_border_culled_by_water() {
+        let mut center = Chunk::default();
+        let mut neighbor = Chunk::default();
+        center.set(CHUNK_SIZE - 1, 0, 0, builtin_voxel("water"));
+        neighbor.set(0, 0, 0, builtin_voxel("water"));
+        let padded = PaddedChunk::new(&center, |offset| (offset == IVec3::X).then_some(&neighbor));
+
+        let quads = generate_culled_mesh(&padded, MeshPass::Transparent);
+        assert!(quads.len() == 5);
+        assert!(quads.iter().all(|quad| quad.face != VoxelFace::Rig));
+    }
+}
+
+#[cfg(test)]
+mod transparent_pass {
+    use super::*;
+    use crate::block::builtin_voxel;
+    use crate::chunk::Chunk;
+    use crate::chunk::Chunked;
+
+    #[test]
+    fn semi_blocks_split_from_opaque() {
+        let mut chunk = Chunk::default();
+        chunk.set(0, 0, 0, builtin_voxel("stone"));
+        chunk.set(1, 0, 0, builtin_voxel("water"));
+
+        let mesh = generate_mesh(&PaddedChunk::new(&chunk, |_| None), MeshingMode::Culled);
+        assert!(mesh.opaque.len() == 6);
+        assert!(mesh.transparent.len() == 5);
+        assert!(mesh.opaque.iter().all(|quad| quad.block == builtin_voxel("stone")));
+        assert!(mesh.transparent.iter().all(|quad| quad.block == builtin_voxel("water")));
+    }
+
+    #[test]
+    fn flowing_surface_is_lowered() {
+        let water = builtin_voxel("water");
+        let mut chunk = Chunk::default();
+        for y in 0..2 {
+            chunk.set(3, y, 3, water);
+            chunk.set_flow(3, y, 3, 4);
+        }
+
+        let quads = generate_culled_mesh(&PaddedChunk::new(&chunk, |_| None), MeshPass::Transparent);
+        let top = quads.iter().find(|quad| quad.face == VoxelFace::Top).unwrap();
+        assert!(top.positions(1.).iter().all(|corner| corner.y == 1.5));
+        // the voxel under the surface is covered by the same fluid and keeps its full height
+        let lower_side =
+            quads.iter().find(|quad| quad.face == VoxelFace::Rig && quad.vox_loc[1] == 0).unwrap();
+        assert!(lower_side.positions(1.).iter().any(|corner| corner.y == 1.));
+    }
+
+    #[test]
+    fn step_between_flow_levels_is_closed() {
+        let water = builtin_voxel("water");
+        let mut chunk = Chunk::default();
+        chunk.set(3, 0, 3, water);
+        chunk.set_flow(3, 0, 3, 2);
+        chunk.set(4, 0, 3, water);
+        chunk.set_flow(4, 0, 3, 6);
+
+        for mode in [MeshingMode::Culled, MeshingMode::Greedy] {
+            let quads = generate_mesh(&PaddedChunk::new(&chunk, |_| None), mode).transparent;
+            let step =
+                quads.iter().find(|quad| quad.face == VoxelFace::Rig && quad.vox_loc == [3, 0, 3]).unwrap();
+            let heights: Vec<f32> = step.positions(1.).iter().map(|corner| corner.y).collect();
+            assert!(heights.contains(&0.25) && heights.contains(&0.75));
+            assert!(quads.iter().all(|quad| quad.face != VoxelFace::Lef || quad.vox_loc != [4, 0, 3]));
+        }
+    }
+
+    #[test]
+    fn semi_faces_cull_only_matching_blocks() {
+        let mut chunk = Chunk::default();
+        chunk.set(0, 0, 0, builtin_voxel("water"));
+        chunk.set(1, 0, 0, builtin_voxel("water"));
+        chunk.set(2, 0, 0, builtin_voxel("leaf"));
+
+        let quads = generate_culled_mesh(&PaddedChunk::new(&chunk, |_| None), MeshPass::Transparent);
+        let water = quads.iter().filter(|quad| quad.block == builtin_voxel("water")).count();
+        let leaf = quads.iter().filter(|quad| quad.block == builtin_voxel("leaf")).count();
+        assert!(water == 10);
+        assert!(leaf == 6);
+    }
+
+    #[test]
+    fn water_excluded_from_collision() {
+        let mut chunk = Chunk::default();
+        chunk.set(0, 0, 0, builtin_voxel("water"));
+        chunk.set(0, 2, 0, builtin_voxel("leaf"));
+
+        let registry = BlockRegistry::default();
+        let mesh = generate_mesh(&PaddedChunk::new(&chunk, |_| None), MeshingMode::Greedy);
+        assert!(mesh.collision_quads(&registry).count() == 6);
+        assert!(mesh.collision_quads(&registry).all(|quad| quad.block == builtin_voxel("leaf")));
+    }
+}
+
+#[cfg(test)]
+mod ambient_occlusion {
+    use super::*;
+    use crate::block::builtin_voxel;
+    use crate::chunk::Chunk;
+    use crate::chunk::Chunked;
+
+    #[test]
+    fn open_face_is_unoccluded() {
+        let mut chunk = Chunk::default();
+        chunk.set(4, 4, 4, builtin_voxel("stone"));
+
+        let padded = PaddedChunk::new(&chunk, |_| None);
+        assert!(corner_occlusion(&padded, [4, 4, 4], VoxelFace::Top) == [3; 4]);
+    }
+
+    #[test]
+    fn wall_darkens_adjacent_corners() {
+        let mut chunk = Chunk::default();
+        chunk.set(4, 4, 4, builtin_voxel("stone"));
+        chunk.set(5, 5, 4, builtin_voxel("stone"));
+        chunk.set(5, 5, 5, builtin_voxel("stone"));
+        chunk.set(4, 5, 5, builtin_voxel("stone"));
+
+        let padded = PaddedChunk::new(&chunk, |_| None);
+        let occlusion = corner_occlusion(&padded, [4, 4, 4], VoxelFace::Top);
+        // corners in the order of `VoxelFace::corners`, the +x +z corner is boxed in by both edges
+        assert!(occlusion == [3, 2, 2, 0]);
+    }
+
+    #[test]
+    fn triangulation_follows_occlusion() {
+        let mut quad = Quad {
+            vox_loc: [0; 3],
+            size: [1; 3],
+            face: VoxelFace::Top,
+            block: builtin_voxel("stone"),
+            light: 0,
+            occlusion: [3; 4],
+            drop: 0,
+            rise: 0,
+        };
+        let even = quad.indices(0);
+
+        quad.occlusion = [3, 0, 3, 3];
+        assert!(quad.indices(0) != even);
+        assert!(quad.indices(0).iter().filter(|&&index| index == 0).count() == 2);
+    }
+}
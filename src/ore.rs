@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::block::BlockRegistry;
+use crate::block::RegistryError;
+use crate::block::Voxel;
+use crate::config::blocks::CHUNK_SIZE;
+use crate::settings::OreSettings;
+
+/// an ore from the settings with its block looked up
+#[derive(Debug, Clone)]
+pub struct Ore {
+    pub voxel: Voxel,
+    settings: OreSettings,
+}
+
+impl Ore {
+    pub fn new(registry: &BlockRegistry, settings: &OreSettings) -> Result<Self, RegistryError> {
+        Ok(Self { voxel: registry.require(&settings.block)?, settings: settings.clone() })
+    }
+
+    /// world positions of the veins starting in the chunk at origin, each vein starts at a random voxel of the
+    /// chunk within the height range and wanders a voxel at a time from there, whatever wanders out of the
+    /// height range is dropped, a vein can wander into the chunks around its own so those replay it to find
+    /// the ore that lands in them
+    pub fn veins(&self, origin: IVec3, rng: &mut impl Rng) -> Vec<IVec3> {
+        let size = CHUNK_SIZE as i32;
+        let heights =
+            self.settings.min_height.max(origin.y)..=self.settings.max_height.min(origin.y + size - 1);
+        if heights.is_empty() {
+            return Vec::new();
+        }
+
+        let mut positions = Vec::new();
+        for _ in 0..self.settings.veins_per_chunk {
+            let mut position = IVec3::new(
+                origin.x + rng.random_range(0..size),
+                rng.random_range(heights.clone()),
+                origin.z + rng.random_range(0..size),
+            );
+            for _ in 0..self.settings.vein_size {
+                if (self.settings.min_height..=self.settings.max_height).contains(&position.y) {
+                    positions.push(position);
+                }
+
+                let mut step = IVec3::ZERO;
+                step[rng.random_range(0..3)] = if rng.random_bool(0.5) { 1 } else { -1 };
+                position += step;
+            }
+        }
+
+        positions
+    }
+}
+
+#[cfg(test)]
+mod ore_veins {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn coal(vein_size: u32, veins_per_chunk: u32, min_height: i32, max_height: i32) -> Ore {
+        let settings =
+            OreSettings { block: "coal".to_string(), vein_size, veins_per_chunk, min_height, max_height };
+        Ore::new(&BlockRegistry::default(), &settings).unwrap()
+    }
+
+    #[test]
+    fn veins_stay_near_chunk_and_in_height_range() {
+        let ore = coal(20, 6, -10, 5);
+        let origin = IVec3::new(-32, -32, 64);
+        let positions = ore.veins(origin, &mut StdRng::seed_from_u64(4));
+
+        assert!(!positions.is_empty());
+        assert!(positions.len() <= 6 * 20);
+        for position in positions {
+            let local = position - origin;
+            assert!(
+                local.cmpgt(IVec3::splat(-20)).all()
+                    && local.cmplt(IVec3::splat(CHUNK_SIZE as i32 + 20)).all()
+            );
+            assert!((-10..=5).contains(&position.y));
+        }
+    }
+
+    #[test]
+    fn veins_are_clustered() {
+        let positions = coal(12, 1, -64, 64).veins(IVec3::ZERO, &mut StdRng::seed_from_u64(9));
+        let first = positions[0];
+        assert!(positions.iter().all(|&position| (position - first).abs().max_element() < 12));
+    }
+
+    #[test]
+    fn nothing_outside_height_range() {
+        let ore = coal(8, 6, 40, 60);
+        assert!(ore.veins(IVec3::ZERO, &mut StdRng::seed_from_u64(1)).is_empty());
+    }
+
+    #[test]
+    fn unknown_block_rejected() {
+        let settings = OreSettings { block: "mithril".to_string(), ..OreSettings::default() };
+        assert!(Ore::new(&BlockRegistry::default(), &settings).is_err());
+    }
+}
@@ -5,43 +5,41 @@ use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use bevy_rapier3d::prelude::*;
 
-use crate::block::BlockType;
 use crate::block::Voxel;
-use crate::block::get_block;
 use crate::config::blocks::VOXEL_SIZE;
-use crate::config::keys::CAMERA_CYCLE;
-use crate::config::keys::CYCLE_BLOCK_DOWN;
-use crate::config::keys::CYCLE_BLOCK_UP;
-use crate::config::keys::JUMP;
-use crate::config::keys::PLAYER_RESET;
-use crate::config::keys::WALK_BAC;
-use crate::config::keys::WALK_DOW;
-use crate::config::keys::WALK_FOR;
-use crate::config::keys::WALK_LEF;
-use crate::config::keys::WALK_RIG;
-use crate::config::keys::WALK_UPW;
-use crate::config::player::BLOCK_REACH;
+use crate::fog::FogPlugin;
+use crate::highlight::TargetHighlightPlugin;
+use crate::hotbar::Hotbar;
+use crate::hotbar::HotbarPlugin;
+use crate::hotbar::inventory_closed;
+use crate::input::Action;
+use crate::input::Actions;
+use crate::input::InputContext;
+use crate::raycast::VoxelHit;
+use crate::settings::Settings;
 use crate::skybox::SkyBoxAttachment;
 use crate::skybox::SkyBoxPlugin;
 use crate::world::BlockBreakEvent;
 use crate::world::BlockPlaceEvent;
+use crate::world::WorldChunks;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(SkyBoxPlugin);
-        app.init_resource::<BlockSelection>();
+        app.add_plugins(TargetHighlightPlugin);
+        app.add_plugins(FogPlugin);
+        app.add_plugins(HotbarPlugin);
+        app.init_resource::<BlockTarget>();
         app.add_systems(Startup, player_setup);
-        app.add_systems(Startup, player_block_ui);
         app.add_systems(Startup, player_flycamera_setup);
         app.add_systems(Update, player_camera_config_swap);
         app.add_systems(Update, player_flycamera_move);
-        app.add_systems(Update, player_block_ui_update);
-        app.add_systems(Update, player_block_select);
-        app.add_systems(Update, player_look);
+        app.add_systems(Update, player_look.run_if(inventory_closed));
         app.add_systems(Update, player_move);
-        app.add_systems(Update, player_interact);
+        app.add_systems(Update, player_target.after(player_look).after(player_move));
+        app.add_systems(Update, player_interact.after(player_target).run_if(inventory_closed));
         app.add_systems(Update, player_reset);
     }
 }
@@ -66,6 +64,7 @@ fn player_setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    settings: Res<Settings>,
 ) {
     let player_collider = commands
         .spawn(Player {
@@ -90,14 +89,18 @@ fn player_setup(
         .insert(Camera3d::default())
         .insert(Camera { ..Default::default() })
         .insert(Msaa::Off)
-        .insert(ScreenSpaceAmbientOcclusion {
-            quality_level: ScreenSpaceAmbientOcclusionQualityLevel::Medium,
-            ..Default::default()
-        })
         .insert(SkyBoxAttachment)
         .insert(Transform::from_xyz(0., 0.8, 0.))
         .id();
 
+    // the chunk meshes carry baked corner occlusion, so screen space occlusion is only extra detail
+    if settings.graphics.ssao_enabled {
+        commands.entity(player_camera).insert(ScreenSpaceAmbientOcclusion {
+            quality_level: ScreenSpaceAmbientOcclusionQualityLevel::Medium,
+            ..Default::default()
+        });
+    }
+
     commands
         .spawn(Text::new("+"))
         .insert(TextColor::BLACK)
@@ -124,19 +127,22 @@ fn player_camera_config_swap(
     player_collider: Single<(Entity, Option<&Children>), With<Player>>,
     flycamera: Single<Entity, (With<FlyCamera>, Without<Player>)>,
     camera: Single<Entity, With<PlayerCamera>>,
-    keys: Res<ButtonInput<KeyCode>>,
+    mut context: ResMut<InputContext>,
+    actions: Res<Actions>,
 ) {
-    if keys.just_pressed(CAMERA_CYCLE) {
+    if actions.just_pressed(Action::CameraCycle) {
         let (player, player_children) = *player_collider;
         let flycam = *flycamera;
 
         if player_children.map(|child| child.iter().any(|child| child == *camera)).unwrap_or(false) {
             commands.entity(player).remove_children(&[*camera]);
             commands.entity(flycam).add_child(*camera);
+            *context = InputContext::Flying;
         }
         else {
             commands.entity(flycam).remove_children(&[*camera]);
             commands.entity(player).add_child(*camera);
+            *context = InputContext::Walking;
         }
     }
 }
@@ -145,24 +151,24 @@ fn player_flycamera_move(
     mut player: Single<&mut Transform, With<FlyCamera>>,
     look: Single<&Transform, (With<PlayerCamera>, Without<FlyCamera>)>,
     player_config: Single<&Player, Without<Children>>,
-    keys: Res<ButtonInput<KeyCode>>,
+    actions: Res<Actions>,
 ) {
     let front = look.forward().normalize();
     let right = look.right().normalize();
     let up = look.up().normalize();
-    let mut translation = Vec3::ZERO;
 
-    for key in keys.get_pressed() {
-        match *key {
-            | WALK_FOR => translation += front,
-            | WALK_BAC => translation -= front,
-            | WALK_RIG => translation += right,
-            | WALK_LEF => translation -= right,
-            | WALK_UPW => translation += up,
-            | WALK_DOW => translation -= up,
-            | _ => {}
-        }
-    }
+    let translation = [
+        (Action::MoveForward, front),
+        (Action::MoveBack, -front),
+        (Action::MoveRight, right),
+        (Action::MoveLeft, -right),
+        (Action::MoveUp, up),
+        (Action::MoveDown, -up),
+    ]
+    .into_iter()
+    .filter(|&(action, _)| actions.pressed(action))
+    .map(|(_, direction)| direction)
+    .sum::<Vec3>();
 
     player.translation += translation * player_config.speed;
 }
@@ -195,30 +201,30 @@ fn player_move(
         &Player,
     )>,
     cam_query: Single<&Transform, With<PlayerCamera>>,
-    keys: Res<ButtonInput<KeyCode>>,
+    actions: Res<Actions>,
     time: Res<Time>,
 ) {
     let (mut controller, mut vertical_velocity, control_output, player) = player_query.into_inner();
     let dt = time.delta_secs();
     let (front, right) = (cam_query.forward().with_y(0.).normalize(), cam_query.right().normalize());
 
-    let mut movement = Vec3::ZERO;
-    for key in keys.get_pressed() {
-        match *key {
-            | WALK_FOR => movement += front,
-            | WALK_BAC => movement -= front,
-            | WALK_RIG => movement += right,
-            | WALK_LEF => movement -= right,
-            | _ => {}
-        }
-    }
+    let movement = [
+        (Action::MoveForward, front),
+        (Action::MoveBack, -front),
+        (Action::MoveRight, right),
+        (Action::MoveLeft, -right),
+    ]
+    .into_iter()
+    .filter(|&(action, _)| actions.pressed(action))
+    .map(|(_, direction)| direction)
+    .sum::<Vec3>();
     let horizontal = movement.normalize_or_zero() * player.speed * dt;
 
     vertical_velocity.value -= player.gravity * dt;
     if control_output.grounded && vertical_velocity.value.is_sign_negative() {
         vertical_velocity.value = 0.;
     }
-    if keys.pressed(JUMP) && control_output.grounded && vertical_velocity.value < 0.5 {
+    if actions.pressed(Action::Jump) && control_output.grounded && vertical_velocity.value < 0.5 {
         vertical_velocity.value = player.jump_velocity;
     }
 
@@ -227,84 +233,46 @@ fn player_move(
     controller.translation = Some(total_movemnt);
 }
 
-fn player_interact(
-    mut break_events: EventWriter<BlockBreakEvent>,
-    mut place_events: EventWriter<BlockPlaceEvent>,
+/// the block the camera is aimed at, refreshed every frame so interaction and the highlight agree
+#[derive(Default, Resource)]
+pub struct BlockTarget {
+    pub hit: Option<VoxelHit>,
+}
+
+fn player_target(
+    mut target: ResMut<BlockTarget>,
     player_transform: Single<&GlobalTransform, With<PlayerCamera>>,
-    player_collider: Single<Entity, With<Player>>,
-    player_block: Res<BlockSelection>,
-    context: ReadRapierContext,
-    mouse: Res<ButtonInput<MouseButton>>,
+    world: Res<WorldChunks>,
+    settings: Res<Settings>,
 ) {
-    let Ok(context) = context.single()
-    else {
-        error!("failed to get rapier context");
-        return;
-    };
-
-    if let Some(ray_hit) = context.cast_ray_and_get_normal(
-        player_transform.translation(),
-        player_transform.forward().as_vec3(),
-        BLOCK_REACH,
-        true,
-        QueryFilter::new().exclude_collider(player_collider.into_inner()),
-    ) {
-        let (.., hit) = ray_hit;
+    let origin = player_transform.translation() / VOXEL_SIZE;
+    let direction = player_transform.forward().as_vec3();
 
-        let break_pos = (hit.point / VOXEL_SIZE - hit.normal * VOXEL_SIZE / 100.).floor().as_ivec3();
-        let place_pos = (hit.point / VOXEL_SIZE + hit.normal * VOXEL_SIZE / 100.).floor().as_ivec3();
+    target.hit = world.raycast(origin, direction, settings.player.block_reach / VOXEL_SIZE);
+}
 
-        if mouse.just_pressed(MouseButton::Left) {
-            break_events.write(BlockBreakEvent { position: break_pos });
+fn player_interact(
+    mut break_events: EventWriter<BlockBreakEvent>,
+    mut place_events: EventWriter<BlockPlaceEvent>,
+    hotbar: Res<Hotbar>,
+    target: Res<BlockTarget>,
+    actions: Res<Actions>,
+) {
+    if let Some(hit) = target.hit {
+        if actions.just_pressed(Action::BreakBlock) {
+            break_events.write(BlockBreakEvent { position: hit.position });
         }
-        if mouse.just_pressed(MouseButton::Right) {
-            place_events.write(BlockPlaceEvent { position: place_pos, species: player_block.block });
+        // an empty slot has nothing to place
+        if actions.just_pressed(Action::PlaceBlock) && hotbar.block() != Voxel::Empty {
+            place_events.write(BlockPlaceEvent { position: hit.placement(), species: hotbar.block() });
         }
     }
 }
 
-fn player_reset(mut query: Query<&mut Transform, With<Player>>, keys: Res<ButtonInput<KeyCode>>) {
-    if keys.just_pressed(PLAYER_RESET) {
+fn player_reset(mut query: Query<&mut Transform, With<Player>>, actions: Res<Actions>) {
+    if actions.just_pressed(Action::PlayerReset) {
         for mut transform in &mut query {
             *transform = transform.with_translation(Vec3::new(3., 20., 3.));
         }
     }
 }
-
-#[derive(Default, Resource)]
-struct BlockSelection {
-    block: Voxel,
-    index: usize,
-}
-
-fn player_block_select(mut block: ResMut<BlockSelection>, keys: Res<ButtonInput<KeyCode>>) {
-    if keys.just_pressed(CYCLE_BLOCK_UP) {
-        block.index = block.index.wrapping_add(1);
-        block.block = get_block(block.index);
-    }
-    if keys.just_pressed(CYCLE_BLOCK_DOWN) {
-        block.index = block.index.wrapping_sub(1);
-        block.block = get_block(block.index);
-    }
-}
-
-#[derive(Component)]
-struct PlayerUI;
-
-fn player_block_ui(mut commands: Commands) {
-    commands
-        .spawn(PlayerUI)
-        .insert(Text::from(String::from(BlockType::default())))
-        .insert(TextFont { font_size: 15., ..Default::default() })
-        .insert(TextColor::BLACK)
-        .insert(Node {
-            position_type: PositionType::Absolute,
-            top: Val::Px(5.),
-            left: Val::Px(10.),
-            ..Default::default()
-        });
-}
-
-fn player_block_ui_update(text: Single<&mut Text, With<PlayerUI>>, block: ResMut<BlockSelection>) {
-    *text.into_inner() = Text::from(String::from(block.block));
-}
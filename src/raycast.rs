@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+
+/// the first voxel along a ray that the lookup reported as solid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoxelHit {
+    pub position: IVec3,
+    /// outward normal of the face the ray entered through, zero when the ray starts inside the voxel
+    pub normal: IVec3,
+    pub distance: f32,
+}
+
+impl VoxelHit {
+    /// the cell in front of the hit face, where a placed block goes
+    pub fn placement(&self) -> IVec3 {
+        self.position + self.normal
+    }
+}
+
+/// walks the voxel grid one cell at a time along the ray (Amanatides & Woo), so every cell the ray passes
+/// through is visited exactly once and in order, positions and reach are in voxels
+pub fn raycast(
+    origin: Vec3,
+    direction: Vec3,
+    reach: f32,
+    mut solid: impl FnMut(IVec3) -> bool,
+) -> Option<VoxelHit> {
+    let direction = direction.try_normalize()?;
+    let mut position = origin.floor().as_ivec3();
+
+    // distance along the ray to the next cell boundary on each axis, and between consecutive boundaries
+    let mut step = IVec3::ZERO;
+    let mut next = Vec3::INFINITY;
+    let mut delta = Vec3::INFINITY;
+    for axis in 0..3 {
+        if direction[axis] > 0. {
+            step[axis] = 1;
+            delta[axis] = 1. / direction[axis];
+            next[axis] = (position[axis] as f32 + 1. - origin[axis]) * delta[axis];
+        }
+        else if direction[axis] < 0. {
+            step[axis] = -1;
+            delta[axis] = -1. / direction[axis];
+            next[axis] = (origin[axis] - position[axis] as f32) * delta[axis];
+        }
+    }
+
+    let mut normal = IVec3::ZERO;
+    let mut distance = 0.;
+    while distance <= reach {
+        if solid(position) {
+            return Some(VoxelHit { position, normal, distance });
+        }
+
+        let axis = match (next.x < next.y, next.x < next.z, next.y < next.z) {
+            | (true, true, _) => 0,
+            | (false, _, true) => 1,
+            | _ => 2,
+        };
+        distance = next[axis];
+        next[axis] += delta[axis];
+        position[axis] += step[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod voxel_raycast {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn blocks(positions: &[IVec3]) -> impl Fn(IVec3) -> bool {
+        let solid: HashSet<IVec3> = positions.iter().copied().collect();
+        move |position| solid.contains(&position)
+    }
+
+    #[test]
+    fn hits_face_along_axis() {
+        let hit = raycast(Vec3::new(0.5, 0.5, 0.5), Vec3::X, 8., blocks(&[IVec3::new(4, 0, 0)])).unwrap();
+        assert!(hit.position == IVec3::new(4, 0, 0));
+        assert!(hit.normal == IVec3::NEG_X);
+        assert!(hit.placement() == IVec3::new(3, 0, 0));
+        assert!((hit.distance - 3.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn hits_face_in_negative_direction() {
+        let hit =
+            raycast(Vec3::new(0.5, 3.2, -1.5), Vec3::NEG_Y, 8., blocks(&[IVec3::new(0, -2, -2)])).unwrap();
+        assert!(hit.position == IVec3::new(0, -2, -2));
+        assert!(hit.normal == IVec3::Y);
+        assert!(hit.placement() == IVec3::new(0, -1, -2));
+    }
+
+    #[test]
+    fn visits_every_cell_it_crosses() {
+        let mut visited = Vec::new();
+        raycast(Vec3::new(0.5, 0.25, 0.5), Vec3::new(1., 1., 0.), 3., |position| {
+            visited.push(position);
+            false
+        });
+        for pair in visited.windows(2) {
+            let moved = pair[1] - pair[0];
+            assert!(moved.abs().element_sum() == 1);
+        }
+        assert!(visited.starts_with(&[IVec3::new(0, 0, 0), IVec3::new(1, 0, 0), IVec3::new(1, 1, 0)]));
+    }
+
+    #[test]
+    fn stops_at_reach() {
+        assert!(raycast(Vec3::new(0.5, 0.5, 0.5), Vec3::Z, 2., blocks(&[IVec3::new(0, 0, 4)])).is_none());
+        assert!(raycast(Vec3::new(0.5, 0.5, 0.5), Vec3::ZERO, 2., blocks(&[IVec3::ZERO])).is_none());
+    }
+
+    #[test]
+    fn starting_inside_a_block() {
+        let hit = raycast(Vec3::new(2.5, 2.5, 2.5), Vec3::X, 8., blocks(&[IVec3::splat(2)])).unwrap();
+        assert!(hit.normal == IVec3::ZERO);
+        assert!(hit.placement() == hit.position);
+    }
+}
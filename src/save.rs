@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::block::BlockRegistry;
+use crate::block::Voxel;
+use crate::chunk::Chunk;
+use crate::chunk::Chunked;
+use crate::chunk::SOURCE_FLOW;
+use crate::config::save::REGION_SIZE;
+use crate::config::save::WORLD_DIRECTORY;
+use crate::hotbar::HOTBAR_SLOTS;
+use crate::hotbar::Hotbar;
+use crate::player::Player;
+use crate::settings::Settings;
+use crate::skybox::WorldClock;
+use crate::world::TerrainNoise;
+
+pub struct WorldSavePlugin;
+
+impl Plugin for WorldSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostStartup, metadata_load);
+        app.add_systems(Last, metadata_save_on_exit);
+    }
+}
+
+const REGION_MAGIC: &[u8; 4] = b"VXRG";
+const REGION_VERSION: u8 = 4;
+const REGION_VOLUME: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+const REGION_HEADER: usize = REGION_MAGIC.len() + 1 + REGION_VOLUME * 8;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorldMetadata {
+    seed: u32,
+    player_position: [f32; 3],
+    #[serde(default)]
+    time_of_day: Option<f32>,
+    /// voxel names of the hotbar slots from left to right, `air` for an empty slot
+    #[serde(default)]
+    hotbar: Vec<String>,
+    #[serde(default)]
+    selected_slot: usize,
+}
+
+fn metadata_path() -> PathBuf {
+    PathBuf::from(WORLD_DIRECTORY).join("level.ron")
+}
+
+fn region_path(region: IVec3) -> PathBuf {
+    PathBuf::from(WORLD_DIRECTORY)
+        .join("regions")
+        .join(format!("r.{}.{}.{}.region", region.x, region.y, region.z))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// a region file too short for what its header points at is broken rather than unreadable
+fn truncated(message: &str) -> impl FnOnce(io::Error) -> io::Error + '_ {
+    move |err| match err.kind() {
+        | io::ErrorKind::UnexpectedEof => invalid_data(message),
+        | _ => err,
+    }
+}
+
+/// splits a chunk position into its region and the slot it occupies in that region's table
+fn region_slot(chunk_pos: IVec3) -> (IVec3, usize) {
+    let region = chunk_pos.div_euclid(IVec3::splat(REGION_SIZE));
+    let [x, y, z] = chunk_pos.rem_euclid(IVec3::splat(REGION_SIZE)).to_array().map(|value| value as usize);
+    let size = REGION_SIZE as usize;
+
+    (region, size * size * z + size * y + x)
+}
+
+/// chunks are stored as the voxel names of the blocks they contain followed by one index into those names per
+/// voxel, so saves don't depend on the ids the block registry happened to hand out
+fn encode_chunk(chunk: &Chunk, registry: &BlockRegistry) -> io::Result<Vec<u8>> {
+    let kinds: Vec<Voxel> = chunk.kinds().collect();
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&(kinds.len() as u16).to_le_bytes())?;
+    for &voxel in &kinds {
+        let name = registry.voxel_name(voxel).to_string();
+        let length = u8::try_from(name.len()).map_err(|_| invalid_data("block name is too long to save"))?;
+        encoder.write_all(&[length])?;
+        encoder.write_all(name.as_bytes())?;
+    }
+
+    for voxel in chunk.iter() {
+        let index = kinds.iter().position(|&kind| kind == voxel).expect("chunk kinds cover every voxel");
+        encoder.write_all(&(index as u16).to_le_bytes())?;
+    }
+    // flow levels follow the voxels, one byte each, they compress away for chunks without running fluid
+    for level in chunk.flows() {
+        encoder.write_all(&[level])?;
+    }
+
+    encoder.finish()
+}
+
+fn take<'a>(data: &mut &'a [u8], length: usize) -> io::Result<&'a [u8]> {
+    let (taken, rest) = data.split_at_checked(length).ok_or_else(|| invalid_data("chunk data ends early"))?;
+    *data = rest;
+
+    Ok(taken)
+}
+
+/// only chunks in the current version are read, anything else is rejected rather than guessed at
+fn decode_chunk(data: &[u8], version: u8, registry: &BlockRegistry) -> io::Result<Chunk> {
+    if version != REGION_VERSION {
+        return Err(invalid_data("unsupported chunk version"));
+    }
+    let mut bytes = Vec::new();
+    DeflateDecoder::new(data).read_to_end(&mut bytes)?;
+    let mut data = bytes.as_slice();
+
+    let kinds = u16::from_le_bytes(take(&mut data, 2)?.try_into().expect("took 2 bytes"));
+    let mut palette = Vec::with_capacity(kinds as usize);
+    for _ in 0..kinds {
+        let length = take(&mut data, 1)?[0] as usize;
+        let name = std::str::from_utf8(take(&mut data, length)?)
+            .map_err(|_| invalid_data("block name is not valid utf-8"))?;
+        let voxel =
+            registry.parse_voxel(name).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        palette.push(voxel);
+    }
+
+    if data.len() != Chunk::size() * 3 {
+        return Err(invalid_data("chunk data has the wrong length"));
+    }
+
+    let (voxels, flows) = data.split_at(Chunk::size() * 2);
+    let mut chunk = voxels
+        .chunks_exact(2)
+        .map(|index| {
+            let index = u16::from_le_bytes(index.try_into().expect("chunks are 2 bytes"));
+            palette.get(index as usize).copied().ok_or_else(|| invalid_data("voxel refers to a missing block"))
+        })
+        .collect::<io::Result<Chunk>>()?;
+    for (index, &level) in flows.iter().enumerate().filter(|(_, level)| **level != SOURCE_FLOW) {
+        let (x, y, z) = Chunk::delinearize(index);
+        chunk.set_flow(x, y, z, level);
+    }
+
+    Ok(chunk)
+}
+
+/// checks the magic at the start of a region file and returns the version that follows it
+fn region_version(prefix: &[u8]) -> io::Result<u8> {
+    if &prefix[..REGION_MAGIC.len()] != REGION_MAGIC {
+        return Err(invalid_data("not a region file"));
+    }
+    let version = prefix[REGION_MAGIC.len()];
+    if version != REGION_VERSION {
+        return Err(invalid_data("unsupported region version"));
+    }
+
+    Ok(version)
+}
+
+/// the offset and length stored in one 8 byte entry of the region table
+fn table_entry(entry: &[u8]) -> (usize, usize) {
+    let offset = u32::from_le_bytes(entry[..4].try_into().expect("entry is 8 bytes")) as usize;
+    let length = u32::from_le_bytes(entry[4..].try_into().expect("entry is 8 bytes")) as usize;
+
+    (offset, length)
+}
+
+/// region files are a table of `(offset, length)` pairs, one per chunk slot, followed by the compressed
+/// chunks, a length of zero means the chunk was never saved
+fn read_region(region: IVec3) -> io::Result<Vec<Option<Vec<u8>>>> {
+    let data = match fs::read(region_path(region)) {
+        | Ok(data) => data,
+        | Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![None; REGION_VOLUME]),
+        | Err(err) => return Err(err),
+    };
+
+    if data.len() < REGION_HEADER {
+        return Err(invalid_data("not a region file"));
+    }
+    region_version(&data[..REGION_MAGIC.len() + 1])?;
+
+    let table = &data[REGION_MAGIC.len() + 1..REGION_HEADER];
+    table
+        .chunks_exact(8)
+        .map(|entry| {
+            let (offset, length) = table_entry(entry);
+            match length {
+                | 0 => Ok(None),
+                | _ => data
+                    .get(offset..offset + length)
+                    .map(|slice| Some(slice.to_vec()))
+                    .ok_or_else(|| invalid_data("region entry out of bounds")),
+            }
+        })
+        .collect()
+}
+
+fn write_region(region: IVec3, entries: &[Option<Vec<u8>>]) -> io::Result<()> {
+    let mut table = Vec::with_capacity(REGION_VOLUME * 8);
+    let mut body = Vec::new();
+    for entry in entries {
+        let (offset, length) = match entry {
+            | Some(data) => (REGION_HEADER + body.len(), data.len()),
+            | None => (0, 0),
+        };
+        table.extend_from_slice(&(offset as u32).to_le_bytes());
+        table.extend_from_slice(&(length as u32).to_le_bytes());
+        if let Some(data) = entry {
+            body.extend_from_slice(data);
+        }
+    }
+
+    let path = region_path(region);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // written beside the real file and renamed over it, so generation tasks reading the region from
+    // another thread never see it half written
+    let staging = path.with_extension("region.tmp");
+    let mut file = fs::File::create(&staging)?;
+    file.write_all(REGION_MAGIC)?;
+    file.write_all(&[REGION_VERSION])?;
+    file.write_all(&table)?;
+    file.write_all(&body)?;
+    file.sync_all()?;
+
+    fs::rename(staging, path)
+}
+
+/// reads a single chunk by seeking to its table entry and then to its data, leaving the rest of the region
+/// on disk
+pub fn load_chunk(chunk_pos: IVec3, registry: &BlockRegistry) -> io::Result<Option<Chunk>> {
+    let (region, slot) = region_slot(chunk_pos);
+    let mut file = match fs::File::open(region_path(region)) {
+        | Ok(file) => file,
+        | Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        | Err(err) => return Err(err),
+    };
+    let mut prefix = [0; REGION_MAGIC.len() + 1];
+    file.read_exact(&mut prefix).map_err(truncated("not a region file"))?;
+    let version = region_version(&prefix)?;
+
+    let mut entry = [0; 8];
+    file.seek(SeekFrom::Start((prefix.len() + slot * 8) as u64))?;
+    file.read_exact(&mut entry).map_err(truncated("not a region file"))?;
+    let (offset, length) = table_entry(&entry);
+    if length == 0 {
+        return Ok(None);
+    }
+
+    // the entry is checked against the file before anything is allocated, a corrupt table can claim gigabytes
+    if (offset as u64).saturating_add(length as u64) > file.metadata()?.len() {
+        return Err(invalid_data("region entry out of bounds"));
+    }
+    let mut data = vec![0; length];
+    file.seek(SeekFrom::Start(offset as u64))?;
+    file.read_exact(&mut data).map_err(truncated("region entry out of bounds"))?;
+
+    decode_chunk(&data, version, registry).map(Some)
+}
+
+pub fn save_chunks<'a>(
+    chunks: impl IntoIterator<Item = (IVec3, &'a Chunk)>,
+    registry: &BlockRegistry,
+) -> io::Result<()> {
+    let mut regions: HashMap<IVec3, Vec<(usize, Vec<u8>)>> = HashMap::new();
+    for (chunk_pos, chunk) in chunks {
+        let (region, slot) = region_slot(chunk_pos);
+        regions.entry(region).or_default().push((slot, encode_chunk(chunk, registry)?));
+    }
+
+    for (region, updates) in regions {
+        let mut entries = read_region(region)?;
+        for (slot, data) in updates {
+            entries[slot] = Some(data);
+        }
+        write_region(region, &entries)?;
+    }
+
+    Ok(())
+}
+
+fn metadata_load(
+    mut terrain_noise: ResMut<TerrainNoise>,
+    mut hotbar: ResMut<Hotbar>,
+    registry: Res<BlockRegistry>,
+    mut clock: ResMut<WorldClock>,
+    settings: Res<Settings>,
+    mut player: Single<&mut Transform, With<Player>>,
+) {
+    let contents = match fs::read_to_string(metadata_path()) {
+        | Ok(contents) => contents,
+        | Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+        | Err(err) => {
+            error!("failed to read world metadata: {}", err);
+            return;
+        }
+    };
+    let metadata: WorldMetadata = match ron::from_str(&contents) {
+        | Ok(metadata) => metadata,
+        | Err(err) => {
+            error!("failed to parse world metadata: {}", err);
+            return;
+        }
+    };
+
+    *terrain_noise = TerrainNoise::new(metadata.seed, settings.caves.clone());
+    for (slot, name) in metadata.hotbar.iter().take(HOTBAR_SLOTS).enumerate() {
+        hotbar.slots[slot] = registry.parse_voxel(name).unwrap_or_else(|err| {
+            warn!("hotbar slot {} holds `{}`: {}, leaving it empty", slot + 1, name, err);
+            Voxel::Empty
+        });
+    }
+    hotbar.select(metadata.selected_slot);
+    if let Some(time) = metadata.time_of_day {
+        clock.set(time);
+    }
+    player.translation = Vec3::from_array(metadata.player_position);
+    info!("loaded world with seed {}", metadata.seed);
+}
+
+fn metadata_save_on_exit(
+    mut exit: EventReader<AppExit>,
+    terrain_noise: Res<TerrainNoise>,
+    hotbar: Res<Hotbar>,
+    registry: Res<BlockRegistry>,
+    clock: Res<WorldClock>,
+    player: Single<&Transform, With<Player>>,
+) {
+    if exit.read().next().is_none() {
+        return;
+    }
+
+    let metadata = WorldMetadata {
+        seed: terrain_noise.seed(),
+        player_position: player.translation.to_array(),
+        time_of_day: Some(clock.time()),
+        hotbar: hotbar.slots.iter().map(|&voxel| registry.voxel_name(voxel).to_string()).collect(),
+        selected_slot: hotbar.selected(),
+    };
+
+    let result = ron::ser::to_string_pretty(&metadata, ron::ser::PrettyConfig::default())
+        .map_err(|err| io::Error::other(err.to_string()))
+        .and_then(|contents| {
+            fs::create_dir_all(WORLD_DIRECTORY)?;
+            fs::write(metadata_path(), contents)
+        });
+    if let Err(err) = result {
+        error!("failed to save world metadata: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod region_format {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::block::builtin_voxel;
+
+    #[test]
+    fn chunk_round_trips() {
+        let registry = BlockRegistry::default();
+        let blocks: Vec<Voxel> = registry.voxels().collect();
+        let mut chunk: Chunk = (0..Chunk::size())
+            .map(|index| match index % 5 {
+                | 0 => Voxel::Empty,
+                | _ => blocks[index % blocks.len()],
+            })
+            .collect();
+        chunk.set_flow(3, 4, 5, 6);
+
+        let decoded =
+            decode_chunk(&encode_chunk(&chunk, &registry).unwrap(), REGION_VERSION, &registry).unwrap();
+        assert!(decoded == chunk);
+    }
+
+    #[test]
+    fn uniform_chunk_compresses() {
+        let chunk = Chunk::uniform(builtin_voxel("stone"));
+        assert!(encode_chunk(&chunk, &BlockRegistry::default()).unwrap().len() < 1024);
+    }
+
+    #[test]
+    fn unknown_blocks_rejected() {
+        let registry = BlockRegistry::default();
+        let encoded = encode_chunk(&Chunk::uniform(builtin_voxel("stone")), &registry).unwrap();
+        let without_stone = BlockRegistry::parse("[]").unwrap();
+        assert!(decode_chunk(&encoded, REGION_VERSION, &without_stone).is_err());
+    }
+
+    #[test]
+    fn region_slots_are_unique() {
+        let mut seen = HashSet::new();
+        for z in 0..REGION_SIZE {
+            for y in 0..REGION_SIZE {
+                for x in 0..REGION_SIZE {
+                    let (region, slot) = region_slot(IVec3::new(x - REGION_SIZE, y, z));
+                    assert!(region == IVec3::new(-1, 0, 0));
+                    assert!(seen.insert(slot));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_chunk_rejected() {
+        assert!(decode_chunk(&[1, 2, 3], REGION_VERSION, &BlockRegistry::default()).is_err());
+    }
+
+    #[test]
+    fn other_versions_rejected() {
+        let registry = BlockRegistry::default();
+        let encoded = encode_chunk(&Chunk::uniform(builtin_voxel("stone")), &registry).unwrap();
+        assert!(decode_chunk(&encoded, REGION_VERSION - 1, &registry).is_err());
+    }
+}
@@ -0,0 +1,334 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::biome::Biome;
+use crate::config::blocks::CHUNK_SIZE;
+use crate::config::settings::SETTINGS_FILE;
+use crate::input::Bindings;
+
+/// everything that can be changed without recompiling, read from the settings file at startup, every
+/// field is optional in the file and falls back to the value below
+#[derive(Debug, Clone, PartialEq, Resource, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub world: WorldSettings,
+    pub player: PlayerSettings,
+    pub graphics: GraphicsSettings,
+    pub caves: CaveSettings,
+    /// every ore generation places, an ore is any registered block
+    pub ores: Vec<OreSettings>,
+    pub bindings: Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            world: WorldSettings::default(),
+            player: PlayerSettings::default(),
+            graphics: GraphicsSettings::default(),
+            caves: CaveSettings::default(),
+            ores: vec![OreSettings::default()],
+            bindings: Bindings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WorldSettings {
+    /// chunks loaded in every horizontal direction around the player's chunk
+    pub render_distance: usize,
+    pub seed: u32,
+    /// finished chunks handed to the world per frame, the rest wait for the next one
+    pub chunk_apply_budget: usize,
+    /// seconds for a full day and night
+    pub day_length: f32,
+    /// fraction of the day a new world starts at, noon is 0.5
+    pub start_time: f32,
+    /// seconds between two steps of flowing fluid, each step spreads it by one voxel
+    pub fluid_tick: f32,
+    /// lowest and highest layers of chunks every column is stacked from
+    pub min_chunk_y: i32,
+    pub max_chunk_y: i32,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            render_distance: 0,
+            seed: 2293,
+            chunk_apply_budget: 8,
+            day_length: 600.,
+            start_time: 0.35,
+            fluid_tick: 0.25,
+            min_chunk_y: -2,
+            max_chunk_y: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PlayerSettings {
+    pub block_reach: f32,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self { block_reach: 7.5 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CaveSettings {
+    /// between 0 for no caverns and 1 for caverns through much of the underground
+    pub cavern_density: f32,
+    /// between 0 for no tunnels and 1 for wide tunnels everywhere
+    pub tunnel_density: f32,
+    /// voxels of ground left solid under the surface before any cave
+    pub min_depth: u32,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self { cavern_density: 0.3, tunnel_density: 0.5, min_depth: 6 }
+    }
+}
+
+/// veins of about `vein_size` voxels, `veins_per_chunk` of them started in every chunk that reaches into
+/// the world heights between `min_height` and `max_height`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct OreSettings {
+    pub block: String,
+    pub vein_size: u32,
+    pub veins_per_chunk: u32,
+    pub min_height: i32,
+    pub max_height: i32,
+}
+
+impl Default for OreSettings {
+    fn default() -> Self {
+        Self {
+            block: "coal".to_string(),
+            vein_size: 12,
+            veins_per_chunk: 10,
+            min_height: -64,
+            max_height: 48,
+        }
+    }
+}
+
+/// an srgb color written as `(red, green, blue)` with each channel between 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Rgb(pub f32, pub f32, pub f32);
+
+impl From<Rgb> for Color {
+    fn from(Rgb(red, green, blue): Rgb) -> Self {
+        Color::srgb(red, green, blue)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub sun_color: Rgb,
+    pub sun_strength: f32,
+    pub moon_strength: f32,
+    pub ambient_color: Rgb,
+    pub ambient_strength: f32,
+    pub night_ambient_strength: f32,
+    pub ssao_enabled: bool,
+    /// fraction of the fog distance that stays clear
+    pub fog_start: f32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            sun_color: Rgb(1., 0.9, 0.9),
+            sun_strength: 30000.,
+            moon_strength: 1500.,
+            ambient_color: Rgb(1., 0.75, 0.75),
+            ambient_strength: 750.,
+            night_ambient_strength: 80.,
+            ssao_enabled: true,
+            fog_start: 0.6,
+        }
+    }
+}
+
+/// voxels the tallest tree stands above the surface it grows on
+const TREE_HEADROOM: i32 = 8;
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Invalid { setting: &'static str, reason: &'static str },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            | Self::Io(err) => write!(f, "failed to read settings: {}", err),
+            | Self::Parse(err) => write!(f, "failed to parse settings: {}", err),
+            | Self::Invalid { setting, reason } => write!(f, "setting `{}` {}", setting, reason),
+        }
+    }
+}
+
+impl Error for SettingsError {}
+
+impl Settings {
+    pub fn parse(source: &str) -> Result<Self, SettingsError> {
+        let settings: Self = ron::from_str(source).map_err(SettingsError::Parse)?;
+        settings.validate()?;
+
+        Ok(settings)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SettingsError> {
+        Self::parse(&fs::read_to_string(path).map_err(SettingsError::Io)?)
+    }
+
+    fn validate(&self) -> Result<(), SettingsError> {
+        let invalid = |setting, reason| Err(SettingsError::Invalid { setting, reason });
+        let (world, graphics) = (&self.world, &self.graphics);
+
+        if world.render_distance > 32 {
+            return invalid("world.render_distance", "must be at most 32 chunks");
+        }
+        if world.chunk_apply_budget == 0 {
+            return invalid("world.chunk_apply_budget", "must be at least 1");
+        }
+        if world.day_length.is_nan() || world.day_length <= 0. {
+            return invalid("world.day_length", "must be a positive number of seconds");
+        }
+        if !(0. ..1.).contains(&world.start_time) {
+            return invalid("world.start_time", "must be between 0 and 1");
+        }
+        if world.fluid_tick.is_nan() || world.fluid_tick <= 0. {
+            return invalid("world.fluid_tick", "must be a positive number of seconds");
+        }
+        // the world has to hold every surface the terrain can produce, with room for a tree on the highest
+        let surfaces = Biome::surface_range();
+        if world.min_chunk_y > world.max_chunk_y {
+            return invalid("world.min_chunk_y", "must not be above max_chunk_y");
+        }
+        if world.min_chunk_y * (CHUNK_SIZE as i32) > *surfaces.start() {
+            return invalid("world.min_chunk_y", "must reach below the lowest terrain");
+        }
+        if (world.max_chunk_y + 1) * (CHUNK_SIZE as i32) < *surfaces.end() + TREE_HEADROOM {
+            return invalid("world.max_chunk_y", "must leave room above the highest terrain");
+        }
+        let reach = self.player.block_reach;
+        if !(0. ..=64.).contains(&reach) || reach == 0. {
+            return invalid("player.block_reach", "must be between 0 and 64 blocks");
+        }
+
+        let strengths = [
+            ("graphics.sun_strength", graphics.sun_strength),
+            ("graphics.moon_strength", graphics.moon_strength),
+            ("graphics.ambient_strength", graphics.ambient_strength),
+            ("graphics.night_ambient_strength", graphics.night_ambient_strength),
+        ];
+        if let Some((setting, _)) = strengths.iter().find(|(_, strength)| !(0. ..).contains(strength)) {
+            return invalid(setting, "must not be negative");
+        }
+        for (setting, Rgb(red, green, blue)) in
+            [("graphics.sun_color", graphics.sun_color), ("graphics.ambient_color", graphics.ambient_color)]
+        {
+            if [red, green, blue].iter().any(|channel| !(0. ..=1.).contains(channel)) {
+                return invalid(setting, "channels must be between 0 and 1");
+            }
+        }
+        if !(0. ..1.).contains(&graphics.fog_start) {
+            return invalid("graphics.fog_start", "must be between 0 and 1");
+        }
+        for (setting, density) in [
+            ("caves.cavern_density", self.caves.cavern_density),
+            ("caves.tunnel_density", self.caves.tunnel_density),
+        ] {
+            if !(0. ..=1.).contains(&density) {
+                return invalid(setting, "must be between 0 and 1");
+            }
+        }
+        for ore in &self.ores {
+            // veins are only followed into the chunks right around the one they start in
+            if !(1..=CHUNK_SIZE as u32).contains(&ore.vein_size) {
+                return invalid("ores.vein_size", "must be between 1 and 32");
+            }
+            if ore.min_height > ore.max_height {
+                return invalid("ores.min_height", "must not be above max_height");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// a missing settings file just means the defaults, a broken one is reported and ignored
+    pub fn load_or_default() -> Self {
+        match Self::load(SETTINGS_FILE) {
+            | Ok(settings) => {
+                info!("loaded settings from {}", SETTINGS_FILE);
+                settings
+            }
+            | Err(SettingsError::Io(err)) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            | Err(err) => {
+                error!("{}, falling back to the default settings", err);
+                Self::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod settings_file {
+    use super::*;
+
+    #[test]
+    fn shipped_file_matches_defaults() {
+        let shipped = Settings::parse(include_str!("../settings.ron")).unwrap();
+        assert!(shipped == Settings::default());
+    }
+
+    #[test]
+    fn missing_fields_keep_defaults() {
+        let settings = Settings::parse("(world: (render_distance: 6))").unwrap();
+        assert!(settings.world.render_distance == 6);
+        assert!(settings.world.seed == WorldSettings::default().seed);
+        assert!(settings.bindings == Bindings::default());
+    }
+
+    #[test]
+    fn bad_values_rejected() {
+        for source in [
+            "(world: (day_length: 0.0))",
+            "(world: (start_time: 1.5))",
+            "(world: (fluid_tick: 0.0))",
+            "(world: (min_chunk_y: 1, max_chunk_y: 0))",
+            "(world: (min_chunk_y: 0))",
+            "(world: (max_chunk_y: 1))",
+            "(player: (block_reach: -1.0))",
+            "(graphics: (sun_color: (2.0, 0.0, 0.0)))",
+            "(graphics: (sun_strength: -5.0))",
+            "(caves: (tunnel_density: 1.5))",
+            "(ores: [(block: \"coal\", min_height: 10, max_height: 0)])",
+            "(ores: [(block: \"coal\", vein_size: 40)])",
+        ] {
+            assert!(matches!(Settings::parse(source), Err(SettingsError::Invalid { .. })));
+        }
+        assert!(matches!(
+            Settings::parse("(bindings: (walking: { Jump: Key(NotAKey) }))"),
+            Err(SettingsError::Parse(_))
+        ));
+    }
+}
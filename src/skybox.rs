@@ -2,23 +2,81 @@ use bevy::pbr::NotShadowCaster;
 use bevy::pbr::NotShadowReceiver;
 use bevy::prelude::*;
 
-use crate::config::aesthetics::AMBIENT_COLOR;
-use crate::config::aesthetics::AMBIENT_STRENGTH;
+use crate::config::aesthetics::DAWN_COLOR;
+use crate::config::aesthetics::MOON_COLOR;
+use crate::config::aesthetics::NIGHT_AMBIENT_COLOR;
 use crate::config::aesthetics::SKYBOX_SIZE;
-use crate::config::aesthetics::SUN_COLOR;
-use crate::config::aesthetics::SUN_STRENGTH;
+use crate::input::Action;
+use crate::input::Actions;
+use crate::material::SkyMaterial;
+use crate::settings::Settings;
 
 pub struct SkyBoxPlugin;
 
 impl Plugin for SkyBoxPlugin {
     fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<SkyMaterial>::default());
+        app.init_resource::<WorldClock>();
         app.add_systems(Startup, skybox_setup);
         app.add_systems(Update, skybox_follow);
-        app.add_systems(Update, sun_rotate);
-        app.add_systems(Update, sun_attenuate);
+        app.add_systems(Update, clock_tick);
+        app.add_systems(Update, clock_control);
+        app.add_systems(Update, sun_rotate.after(clock_tick).after(clock_control));
+        app.add_systems(Update, sun_attenuate.after(clock_tick).after(clock_control));
+        app.add_systems(PostUpdate, skybox_sun_track.after(TransformSystem::TransformPropagate));
     }
 }
 
+/// time of day as a fraction of a full day, midnight at 0, sunrise at 0.25, noon at 0.5 and sunset at 0.75
+#[derive(Resource)]
+pub struct WorldClock {
+    time: f32,
+    pub paused: bool,
+}
+
+/// a new world starts at the configured time, a saved one picks up where it left off once its metadata loads
+impl FromWorld for WorldClock {
+    fn from_world(world: &mut World) -> Self {
+        Self::new(world.resource::<Settings>().world.start_time)
+    }
+}
+
+impl WorldClock {
+    /// the sun's path is tilted off the east-west plane so noon shadows still fall at an angle
+    const SUN_TILT: f32 = 0.4;
+
+    pub fn new(time: f32) -> Self {
+        Self { time: time.rem_euclid(1.), paused: false }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set(&mut self, time: f32) {
+        self.time = time.rem_euclid(1.);
+    }
+
+    /// unit vector pointing from the world towards the sun, the moon sits opposite it
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.time - 0.25) * std::f32::consts::TAU;
+        Vec3::new(angle.cos(), angle.sin(), Self::SUN_TILT).normalize()
+    }
+
+    /// how much of the sun's light reaches the ground, fading in and out while the sun crosses the horizon
+    pub fn daylight(&self) -> f32 {
+        let elevation = self.sun_direction().y;
+        let t = ((elevation + 0.1) / 0.3).clamp(0., 1.);
+        t * t * (3. - 2. * t)
+    }
+}
+
+#[derive(Component)]
+struct Sun;
+
+#[derive(Component)]
+struct Moon;
+
 #[derive(Component)]
 struct SkyBox;
 
@@ -28,34 +86,42 @@ pub struct SkyBoxAttachment;
 fn skybox_setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+    settings: Res<Settings>,
 ) {
+    let graphics = &settings.graphics;
+
     commands
         .spawn(SkyBox)
-        .insert(Mesh3d(meshes.add(Cuboid::new(SKYBOX_SIZE, SKYBOX_SIZE, SKYBOX_SIZE))))
-        .insert(MeshMaterial3d(materials.add(StandardMaterial {
-            base_color_texture: Some(asset_server.load("sky_side.png")),
-            cull_mode: None,
-            unlit: true,
-            ..Default::default()
-        })))
+        .insert(Mesh3d(meshes.add(Sphere::new(SKYBOX_SIZE / 2.).mesh().uv(32, 16))))
+        .insert(MeshMaterial3d(materials.add(SkyMaterial::default())))
         .insert(NotShadowCaster)
         .insert(NotShadowReceiver)
         .insert(Transform::default());
 
     commands
-        .spawn(DirectionalLight {
-            color: SUN_COLOR,
+        .spawn(Sun)
+        .insert(DirectionalLight {
+            color: graphics.sun_color.into(),
             shadows_enabled: true,
-            illuminance: SUN_STRENGTH,
+            illuminance: graphics.sun_strength,
+            ..Default::default()
+        })
+        .insert(Transform::default());
+
+    commands
+        .spawn(Moon)
+        .insert(DirectionalLight {
+            color: MOON_COLOR,
+            shadows_enabled: false,
+            illuminance: 0.,
             ..Default::default()
         })
-        .insert(Transform::default().looking_at(Vec3::new(0.5, -2., 1.), Vec3::Y));
+        .insert(Transform::default());
 
     commands.insert_resource(AmbientLight {
-        color: AMBIENT_COLOR,
-        brightness: AMBIENT_STRENGTH,
+        color: graphics.ambient_color.into(),
+        brightness: graphics.ambient_strength,
         ..Default::default()
     });
 }
@@ -71,6 +137,101 @@ fn skybox_follow(
     }
 }
 
-fn sun_rotate() {}
+/// the sky is shaded from whichever way the sun light actually points, so anything that moves the light
+/// moves the sun disk with it
+fn skybox_sun_track(
+    sun: Single<(&GlobalTransform, &DirectionalLight), With<Sun>>,
+    skybox: Single<&MeshMaterial3d<SkyMaterial>, With<SkyBox>>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+) {
+    let (sun_transform, sun_light) = sun.into_inner();
+    let Some(material) = materials.get_mut(&skybox.0)
+    else {
+        return;
+    };
+
+    material.sun_direction = -sun_transform.forward().as_vec3();
+    material.sun_color = sun_light.color.into();
+}
+
+fn clock_tick(mut clock: ResMut<WorldClock>, time: Res<Time>, settings: Res<Settings>) {
+    if !clock.paused {
+        let now = clock.time + time.delta_secs() / settings.world.day_length;
+        clock.set(now);
+    }
+}
+
+/// pausing freezes the sun in place, skipping jumps ahead a quarter of a day
+fn clock_control(mut clock: ResMut<WorldClock>, actions: Res<Actions>) {
+    if actions.just_pressed(Action::PauseTime) {
+        clock.paused = !clock.paused;
+        info!("time of day {}", if clock.paused { "paused" } else { "resumed" });
+    }
+    if actions.just_pressed(Action::SkipTime) {
+        let next = (clock.time * 4.).floor() / 4. + 0.25;
+        clock.set(next);
+        info!("time of day set to {:.2}", clock.time);
+    }
+}
 
-fn sun_attenuate() {}
+fn sun_rotate(
+    clock: Res<WorldClock>,
+    mut sun: Single<&mut Transform, (With<Sun>, Without<Moon>)>,
+    mut moon: Single<&mut Transform, (With<Moon>, Without<Sun>)>,
+) {
+    let sun_direction = clock.sun_direction();
+    sun.look_to(-sun_direction, Vec3::Y);
+    moon.look_to(sun_direction, Vec3::Y);
+}
+
+/// the sun warms towards the dawn color near the horizon and hands over to the moon and a dim blue ambient
+/// once it sets
+fn sun_attenuate(
+    clock: Res<WorldClock>,
+    mut ambient: ResMut<AmbientLight>,
+    mut sun: Single<&mut DirectionalLight, (With<Sun>, Without<Moon>)>,
+    mut moon: Single<&mut DirectionalLight, (With<Moon>, Without<Sun>)>,
+    settings: Res<Settings>,
+) {
+    let graphics = &settings.graphics;
+    let daylight = clock.daylight();
+    let warmth = 1. - (clock.sun_direction().y / 0.4).clamp(0., 1.);
+
+    sun.illuminance = graphics.sun_strength * daylight;
+    sun.color = Color::from(graphics.sun_color).mix(&DAWN_COLOR, warmth);
+    sun.shadows_enabled = daylight > 0.;
+    moon.illuminance = graphics.moon_strength * (1. - daylight);
+
+    ambient.color = NIGHT_AMBIENT_COLOR.mix(&graphics.ambient_color.into(), daylight);
+    ambient.brightness = graphics.night_ambient_strength.lerp(graphics.ambient_strength, daylight);
+}
+
+#[cfg(test)]
+mod day_cycle {
+    use super::*;
+
+    #[test]
+    fn sun_follows_the_clock() {
+        let mut clock = WorldClock::new(0.);
+        clock.set(0.5);
+        assert!(clock.sun_direction().y > 0.9);
+        assert!(clock.daylight() == 1.);
+
+        clock.set(0.);
+        assert!(clock.sun_direction().y < -0.9);
+        assert!(clock.daylight() == 0.);
+
+        clock.set(0.25);
+        assert!(clock.sun_direction().y.abs() < 1e-5);
+        assert!(clock.daylight() > 0. && clock.daylight() < 1.);
+    }
+
+    #[test]
+    fn clock_wraps_around() {
+        let mut clock = WorldClock::new(0.);
+        clock.set(1.25);
+        assert!((clock.time() - 0.25).abs() < 1e-6);
+        clock.set(-0.25);
+        assert!((clock.time() - 0.75).abs() < 1e-6);
+    }
+}
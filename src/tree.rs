@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+
+use crate::block::Voxel;
+
+/// voxels of a tree standing on the ground voxel under base, trunk first since it wins where the canopy
+/// would overlap it, the canopy is two wide layers around the top of the trunk and two narrow ones above
+pub fn tree_voxels(base: IVec3, trunk_height: i32, wood: Voxel, leaf: Voxel) -> Vec<(IVec3, Voxel)> {
+    let mut voxels: Vec<(IVec3, Voxel)> = (0..trunk_height).map(|y| (base + IVec3::Y * y, wood)).collect();
+
+    let top = base + IVec3::Y * (trunk_height - 1);
+    for (y, radius) in [(-2, 2_i32), (-1, 2), (0, 1), (1, 1)] {
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                // corners are cut so the canopy reads as round, the top layer is only a plus
+                let corner = x.abs() == radius && z.abs() == radius;
+                if corner && (radius == 2 || y == 1) {
+                    continue;
+                }
+                let position = top + IVec3::new(x, y, z);
+                if y < 1 && x == 0 && z == 0 {
+                    continue;
+                }
+                voxels.push((position, leaf));
+            }
+        }
+    }
+
+    voxels
+}
+
+#[cfg(test)]
+mod tree_shape {
+    use super::*;
+    use crate::block::builtin_voxel;
+
+    #[test]
+    fn trunk_under_canopy() {
+        let [wood, leaf] = [builtin_voxel("wood"), builtin_voxel("leaf")];
+        let base = IVec3::new(4, 10, -3);
+        let voxels = tree_voxels(base, 5, wood, leaf);
+
+        for y in 0..5 {
+            assert!(voxels.contains(&(base + IVec3::Y * y, wood)));
+        }
+        assert!(voxels.contains(&(base + IVec3::Y * 5, leaf)));
+        assert!(!voxels.iter().any(|&(position, voxel)| voxel == leaf
+            && position.xz() == base.xz()
+            && position.y < base.y + 5));
+        assert!(voxels.iter().all(|(position, _)| (position.xz() - base.xz()).abs().max_element() <= 2));
+    }
+
+    #[test]
+    fn no_voxel_written_twice() {
+        let voxels = tree_voxels(IVec3::ZERO, 4, builtin_voxel("wood"), builtin_voxel("leaf"));
+        let mut positions: Vec<IVec3> = voxels.iter().map(|&(position, _)| position).collect();
+        positions.sort_by_key(|position| position.to_array());
+        positions.dedup();
+        assert!(positions.len() == voxels.len());
+    }
+}
@@ -1,50 +1,252 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::io;
+use std::ops::RangeInclusive;
 
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::tasks::AsyncComputeTaskPool;
+use bevy::tasks::Task;
+use bevy::tasks::block_on;
+use bevy::tasks::futures_lite::future;
 use bevy_rapier3d::prelude::Collider;
 use bevy_rapier3d::prelude::RigidBody;
 use noise::NoiseFn;
 use noise::Perlin;
-use rand::random_bool;
-use rand::random_range;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 
-use crate::block::BlockType;
+use crate::biome::Biome;
+use crate::biome::Climate;
+use crate::biome::TerrainColumn;
+use crate::biome::terrain_column;
+use crate::block::BlockRegistry;
+use crate::block::RegistryError;
 use crate::block::Voxel;
+use crate::cave::Caves;
 use crate::chunk::Chunk;
+use crate::chunk::Chunked;
+use crate::chunk::LightChannel;
+use crate::chunk::PaddedChunk;
+use crate::chunk::bordering_chunks;
+use crate::chunk::surrounding_chunks;
+use crate::config::blocks::BLOCK_DEFINITIONS;
 use crate::config::blocks::CHUNK_SIZE;
 use crate::config::blocks::TRI_COLLIDER_MESH;
-use crate::config::world::RENDER_DISTANCE;
+use crate::config::world::SEA_LEVEL;
+use crate::fluid::FluidPlugin;
+use crate::input::Action;
+use crate::input::Actions;
+use crate::light::LightPropagator;
+use crate::light::OPEN_SKY;
+use crate::light::SkyRow;
+use crate::light::light_chunk;
+use crate::light::sky_below;
+use crate::material::AtlasMaterial;
+use crate::material::AtlasTiling;
+use crate::mesher::MeshingMode;
+use crate::mesher::Quad;
 use crate::mesher::build_mesh;
-use crate::mesher::generate_opaque_mesh;
+use crate::mesher::generate_mesh;
+use crate::ore::Ore;
 use crate::player::Player;
+use crate::raycast::VoxelHit;
+use crate::raycast::raycast;
+use crate::save::load_chunk;
+use crate::save::save_chunks;
+use crate::settings::CaveSettings;
+use crate::settings::OreSettings;
+use crate::settings::Settings;
+use crate::tree::tree_voxels;
 
 pub struct WorldChunksPlugin;
 
 impl Plugin for WorldChunksPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<WorldChunks>();
+        let settings = app.world().resource::<Settings>();
+        let max_chunk_y = settings.world.max_chunk_y;
+        let (registry, terrain_blocks) = block_registry_load(&settings.ores);
+        app.add_plugins(MaterialPlugin::<AtlasMaterial>::default());
+        app.add_plugins(FluidPlugin);
+        app.insert_resource(registry);
+        app.insert_resource(terrain_blocks);
+        app.insert_resource(WorldChunks { max_chunk_y, ..default() });
         app.init_resource::<BlockMaterial>();
         app.init_resource::<TerrainNoise>();
+        app.init_resource::<MeshingMode>();
+        app.init_resource::<ChunkTasks>();
         app.add_event::<BlockBreakEvent>();
         app.add_event::<BlockPlaceEvent>();
         app.add_systems(Startup, chunk_resouce_setup);
         app.add_systems(Update, chunk_block_break);
         app.add_systems(Update, chunk_block_place);
         app.add_systems(Update, chunk_load_manager);
+        app.add_systems(Update, chunk_generation_apply.after(chunk_load_manager));
         app.add_systems(Update, chunk_delete_manager);
+        app.add_systems(Update, chunk_save_manager.after(chunk_delete_manager));
+        app.add_systems(Update, chunk_light_spread.after(chunk_generation_apply));
+        app.add_systems(Update, chunk_mesher_toggle);
+        app.add_systems(Update, chunk_memory_report);
+        app.add_systems(
+            Update,
+            chunk_mesh_rebuild
+                .after(chunk_block_break)
+                .after(chunk_block_place)
+                .after(chunk_generation_apply)
+                .after(chunk_light_spread)
+                .after(chunk_delete_manager)
+                .after(chunk_mesher_toggle),
+        );
+        app.add_systems(Update, chunk_mesh_apply.after(chunk_mesh_rebuild));
+        app.add_systems(Last, chunk_save_on_exit);
     }
 }
 
-#[derive(Default, Resource)]
-struct TerrainNoise {
+#[derive(Clone, Resource)]
+pub struct TerrainNoise {
+    seed: u32,
     noise: Perlin,
+    temperature: Perlin,
+    humidity: Perlin,
+    caves: Caves,
+}
+
+impl Default for TerrainNoise {
+    fn default() -> Self {
+        Self::new(0, CaveSettings::default())
+    }
+}
+
+impl TerrainNoise {
+    const COLUMN_SALT: u64 = 0x636f_6c75_6d6e;
+    const ORE_SALT: u64 = 0x6f72_6573;
+    const TREE_SALT: u64 = 0x7472_6565;
+
+    pub fn new(seed: u32, caves: CaveSettings) -> Self {
+        Self {
+            seed,
+            noise: Perlin::new(seed),
+            temperature: Perlin::new(seed.wrapping_add(1)),
+            humidity: Perlin::new(seed.wrapping_add(2)),
+            caves: Caves::new(seed, caves),
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// the height and biome of any world column, loaded or not, generation builds every column from this
+    pub fn column(&self, x: i32, z: i32) -> TerrainColumn {
+        let [i, k] = [x as f64, z as f64];
+        let climate = Climate {
+            temperature: self.temperature.get([i / 600., k / 600.]),
+            humidity: self.humidity.get([i / 600., k / 600.]),
+        };
+        let roughness = self.noise.get([i / 315., k / 315.]).abs() / 2.
+            + self.noise.get([i / 100., k / 100.]).abs() / 2.
+            + self.noise.get([i / 32., k / 32.]).abs().powi(2) / 3.
+            + self.noise.get([i / 16., k / 16.]).abs() / 8.;
+
+        terrain_column(climate, roughness)
+    }
+
+    /// every random choice in generation comes from here, keyed on the seed, the chunk and the stage, so a
+    /// chunk regenerates voxel for voxel after it unloads
+    fn rng(&self, position: IVec3, salt: u64) -> StdRng {
+        let [x, y, z] = position.to_array().map(|value| value as i64 as u64);
+        let hash = (self.seed as u64 ^ salt)
+            ^ x.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ y.wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+            ^ z.wrapping_mul(0x1656_67b1_9e37_79f9);
+
+        StdRng::seed_from_u64(hash)
+    }
+}
+
+/// the blocks terrain generation places, looked up by name once so a missing definition shows up at startup
+#[derive(Clone, Resource)]
+pub struct TerrainBlocks {
+    grass: Voxel,
+    dirt: Voxel,
+    sand: Voxel,
+    wood: Voxel,
+    leaf: Voxel,
+    stone: Voxel,
+    water: Voxel,
+    ores: Vec<Ore>,
+}
+
+impl TerrainBlocks {
+    /// an ore naming a block the registry doesn't have is left out rather than failing the whole terrain
+    pub fn new(registry: &BlockRegistry, ores: &[OreSettings]) -> Result<Self, RegistryError> {
+        Ok(Self {
+            grass: registry.require("grass")?,
+            dirt: registry.require("dirt")?,
+            sand: registry.require("sand")?,
+            wood: registry.require("wood")?,
+            leaf: registry.require("leaf")?,
+            stone: registry.require("stone")?,
+            water: registry.require("water")?,
+            ores: ores
+                .iter()
+                .filter_map(|ore| {
+                    Ore::new(registry, ore).inspect_err(|err| warn!("{}, leaving that ore out", err)).ok()
+                })
+                .collect(),
+        })
+    }
+
+    /// the block on top of a column and the one under it down to the stone
+    fn layers(&self, biome: Biome) -> (Voxel, Voxel) {
+        match biome {
+            | Biome::Ocean | Biome::Beach | Biome::Desert => (self.sand, self.sand),
+            | Biome::Plains | Biome::Forest => (self.grass, self.dirt),
+            | Biome::Mountains => (self.stone, self.stone),
+        }
+    }
+}
+
+impl Default for TerrainBlocks {
+    fn default() -> Self {
+        Self::new(&BlockRegistry::default(), &Settings::default().ores)
+            .expect("builtin blocks cover the terrain")
+    }
+}
+
+/// everything a generation task needs to fill a chunk, either from its save or from the seed
+#[derive(SystemParam)]
+struct TerrainSources<'w> {
+    noise: Res<'w, TerrainNoise>,
+    blocks: Res<'w, TerrainBlocks>,
+    registry: Res<'w, BlockRegistry>,
+}
+
+fn block_registry_load(ores: &[OreSettings]) -> (BlockRegistry, TerrainBlocks) {
+    let loaded = BlockRegistry::load(BLOCK_DEFINITIONS)
+        .and_then(|registry| Ok((TerrainBlocks::new(&registry, ores)?, registry)));
+
+    match loaded {
+        | Ok((terrain_blocks, registry)) => {
+            info!("loaded {} block definitions", registry.voxels().count());
+            (registry, terrain_blocks)
+        }
+        | Err(err) => {
+            error!("{}, falling back to the builtin blocks", err);
+            let registry = BlockRegistry::default();
+            let terrain_blocks =
+                TerrainBlocks::new(&registry, ores).expect("builtin blocks cover the terrain");
+            (registry, terrain_blocks)
+        }
+    }
 }
 
 #[derive(Resource, Default)]
 struct BlockMaterial {
-    opaque_material: Handle<StandardMaterial>,
-    transparent_material: Handle<StandardMaterial>,
+    opaque_material: Handle<AtlasMaterial>,
+    transparent_material: Handle<AtlasMaterial>,
 }
 
 #[derive(Debug, Component)]
@@ -52,9 +254,187 @@ pub struct ChunkMarker {
     pub location: IVec3,
 }
 
+#[derive(Component)]
+struct TransparentChunkMesh;
+
+/// light steps spread across chunk borders in a single frame, whatever is left over waits for the next one
+const LIGHT_STEPS_PER_FRAME: usize = 32768;
+
+/// seconds to wait after a failed save before writing the chunks again
+const SAVE_RETRY_SECONDS: f32 = 5.0;
+
+/// chunks with a task in flight are pending, so they are never queued a second time, only one save runs at a
+/// time since every save rewrites whole regions
+#[derive(Default, Resource)]
+struct ChunkTasks {
+    generating: HashMap<IVec3, Task<LitChunk>>,
+    meshing: HashMap<IVec3, Task<ChunkMeshOutput>>,
+    saving: Option<ChunkSave>,
+    save_retry: Option<Timer>,
+}
+
+/// edited chunks being written to disk, they leave the edited set while the task runs so edits made in the
+/// meantime mark them again
+struct ChunkSave {
+    chunks: Vec<IVec3>,
+    task: Task<io::Result<()>>,
+}
+
+/// a generated or loaded chunk already lit on its own, along with the sky that fell into it
+struct LitChunk {
+    chunk: Chunk,
+    sky_above: SkyRow,
+}
+
+struct ChunkMeshOutput {
+    opaque: Option<Mesh>,
+    transparent: Option<Mesh>,
+    collider: Option<Collider>,
+}
+
+impl ChunkMeshOutput {
+    fn build(padded: &PaddedChunk, mode: MeshingMode, registry: &BlockRegistry) -> Self {
+        let chunk_mesh = generate_mesh(padded, mode);
+        let collision: Vec<&Quad> = chunk_mesh.collision_quads(registry).collect();
+
+        Self {
+            opaque: (!chunk_mesh.opaque.is_empty()).then(|| build_mesh(&chunk_mesh.opaque, registry)),
+            transparent: (!chunk_mesh.transparent.is_empty())
+                .then(|| build_mesh(&chunk_mesh.transparent, registry)),
+            collider: (!collision.is_empty())
+                .then(|| Collider::from_bevy_mesh(&build_mesh(collision, registry), &TRI_COLLIDER_MESH))
+                .flatten(),
+        }
+    }
+}
+
 #[derive(Default, Resource)]
 pub struct WorldChunks {
     pub chunks: HashMap<IVec3, Chunk>,
+    loaded: HashSet<IVec3>,
+    dirty: HashSet<IVec3>,
+    edited: HashSet<IVec3>,
+    /// the highest layer of chunks, which open sky falls into
+    max_chunk_y: i32,
+    /// light still waiting to spread across chunk borders
+    light_queue: VecDeque<(IVec3, LightChannel)>,
+}
+
+impl WorldChunks {
+    /// copies the chunk along with a border from whichever neighbors are currently loaded, unloaded
+    /// neighbors are treated as air so the edge of the world stays closed
+    pub fn padded_chunk(&self, position: IVec3) -> Option<PaddedChunk> {
+        let chunk = self.chunks.get(&position)?;
+
+        Some(PaddedChunk::new(chunk, |offset| {
+            let neighbor = position + offset;
+            self.loaded.contains(&neighbor).then(|| self.chunks.get(&neighbor)).flatten()
+        }))
+    }
+
+    /// the voxel at a position in the world, `None` while its chunk isn't loaded
+    pub fn voxel(&self, position: IVec3) -> Option<Voxel> {
+        let world_position = WorldPosition::get(position);
+        if !self.loaded.contains(&world_position.chunk_location) {
+            return None;
+        }
+        let chunk = self.chunks.get(&world_position.chunk_location)?;
+        let [x, y, z] = world_position.location_in_chunk.to_array().map(|value| value as usize);
+
+        Some(chunk.get(x, y, z))
+    }
+
+    /// the voxel at a position along with its flow level, `None` while its chunk isn't loaded
+    pub fn fluid_cell(&self, position: IVec3) -> Option<(Voxel, u8)> {
+        let world_position = WorldPosition::get(position);
+        if !self.loaded.contains(&world_position.chunk_location) {
+            return None;
+        }
+        let chunk = self.chunks.get(&world_position.chunk_location)?;
+        let [x, y, z] = world_position.location_in_chunk.to_array().map(|value| value as usize);
+
+        Some((chunk.get(x, y, z), chunk.flow(x, y, z)))
+    }
+
+    /// the first non-empty voxel along the ray, origin and reach are in voxels and unloaded chunks are
+    /// treated as air
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, reach: f32) -> Option<VoxelHit> {
+        raycast(origin, direction, reach, |position| {
+            self.voxel(position).is_some_and(|voxel| voxel != Voxel::Empty)
+        })
+    }
+
+    /// chunks edited since they were last written to disk
+    fn unsaved(&self) -> impl Iterator<Item = (IVec3, &Chunk)> {
+        self.edited.iter().filter_map(|&chunk_pos| Some((chunk_pos, self.chunks.get(&chunk_pos)?)))
+    }
+
+    fn mark_neighbors_dirty(&mut self, position: IVec3) {
+        self.dirty.extend(surrounding_chunks(position));
+    }
+
+    /// runs a light update over the loaded chunks and queues every chunk it touched for remeshing
+    fn relight(&mut self, registry: &BlockRegistry, update: impl FnOnce(&mut LightPropagator)) {
+        let mut propagator = LightPropagator::new(&mut self.chunks, &self.loaded, registry, self.max_chunk_y);
+        update(&mut propagator);
+        let touched = propagator.propagate();
+        self.dirty.extend(touched);
+    }
+
+    /// like relight, but picks up the light still queued from earlier and spreads at most the budget of
+    /// steps, keeping the rest queued for the next call
+    fn relight_within(
+        &mut self,
+        registry: &BlockRegistry,
+        budget: usize,
+        update: impl FnOnce(&mut LightPropagator),
+    ) {
+        let mut propagator = LightPropagator::new(&mut self.chunks, &self.loaded, registry, self.max_chunk_y);
+        propagator.resume(std::mem::take(&mut self.light_queue));
+        update(&mut propagator);
+        let (touched, queued) = propagator.propagate_within(budget);
+        self.dirty.extend(touched);
+        self.light_queue = queued;
+    }
+
+    /// writes voxels and flow levels worked out by the fluid simulation, writes into chunks that aren't
+    /// loaded are dropped since the simulation only ever looks at loaded ones
+    pub fn write_fluid(&mut self, registry: &BlockRegistry, writes: Vec<(IVec3, Voxel, u8)>) {
+        let mut changed = Vec::new();
+        for (position, voxel, level) in writes {
+            let world_position = WorldPosition::get(position);
+            // chunks with unsaved edits stay in memory after unloading, they must not change until they load again
+            if !self.loaded.contains(&world_position.chunk_location) {
+                continue;
+            }
+            let Some(chunk) = self.chunks.get_mut(&world_position.chunk_location)
+            else {
+                continue;
+            };
+
+            let [x, y, z] = world_position.location_in_chunk.to_array().map(|value| value as usize);
+            if chunk.get(x, y, z) != voxel {
+                chunk.set(x, y, z, voxel);
+                changed.push(position);
+            }
+            chunk.set_flow(x, y, z, level);
+            self.mark_edited(&world_position);
+        }
+
+        if !changed.is_empty() {
+            self.relight(registry, |light| {
+                for position in changed {
+                    light.voxel_changed(position);
+                }
+            });
+        }
+    }
+
+    /// a voxel on the chunk border also changes the faces and occlusion of the neighbors across that border
+    fn mark_edited(&mut self, world_position: &WorldPosition) {
+        self.edited.insert(world_position.chunk_location);
+        self.dirty.extend(bordering_chunks(world_position.chunk_location, world_position.location_in_chunk));
+    }
 }
 
 #[derive(Event)]
@@ -70,80 +450,179 @@ pub struct BlockPlaceEvent {
 
 fn chunk_resouce_setup(
     mut block_material: ResMut<BlockMaterial>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut materials: ResMut<Assets<AtlasMaterial>>,
     mut terrain_noise: ResMut<TerrainNoise>,
     asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
 ) {
-    block_material.opaque_material = materials.add(StandardMaterial {
-        base_color_texture: Some(asset_server.load("texture_atlas.png")),
-        perceptual_roughness: 0.95,
-        reflectance: 0.003,
-        cull_mode: None,
-        ..Default::default()
+    block_material.opaque_material = materials.add(AtlasMaterial {
+        base: StandardMaterial {
+            base_color_texture: Some(asset_server.load("texture_atlas.png")),
+            perceptual_roughness: 0.95,
+            reflectance: 0.003,
+            cull_mode: None,
+            ..Default::default()
+        },
+        extension: AtlasTiling::default(),
     });
-    block_material.transparent_material = materials.add(StandardMaterial {
-        base_color_texture: Some(asset_server.load("texture_atlas_transparent.png")),
-        perceptual_roughness: 0.95,
-        reflectance: 0.003,
-        cull_mode: None,
-        alpha_mode: AlphaMode::Add,
-        ..Default::default()
+    block_material.transparent_material = materials.add(AtlasMaterial {
+        base: StandardMaterial {
+            base_color_texture: Some(asset_server.load("texture_atlas_transparent.png")),
+            perceptual_roughness: 0.95,
+            reflectance: 0.003,
+            cull_mode: None,
+            alpha_mode: AlphaMode::Add,
+            ..Default::default()
+        },
+        extension: AtlasTiling::default(),
     });
 
-    terrain_noise.noise = Perlin::new(2293);
+    *terrain_noise = TerrainNoise::new(settings.world.seed, settings.caves.clone());
+    info!("generating world with seed {}", terrain_noise.seed());
 }
 
-fn generate_chunk(position: IVec3, noise: &TerrainNoise) -> Chunk {
+fn generate_chunk(position: IVec3, terrain: &TerrainNoise, blocks: &TerrainBlocks) -> Chunk {
     let mut chunk = Chunk::default();
-    let noise = noise.noise;
+    let chunk_bottom = position.y * CHUNK_SIZE as i32;
+
+    // subsurface depth is drawn per column so chunks stacked on top of each other agree on it
+    let mut column_rng = terrain.rng(position.with_y(0), TerrainNoise::COLUMN_SALT);
 
     for local_x in 0..CHUNK_SIZE {
         for local_z in 0..CHUNK_SIZE {
-            let [i, k] = [
-                position.x as f64 * CHUNK_SIZE as f64 + local_x as f64,
-                position.z as f64 * CHUNK_SIZE as f64 + local_z as f64,
-            ];
-            let height_float = noise.get([i / 315., k / 315.]).abs() * CHUNK_SIZE as f64 / 2.
-                + noise.get([i / 100., k / 100.]).abs() * CHUNK_SIZE as f64 / 2.
-                + noise.get([i / 32., k / 32.]).abs().powi(2) * CHUNK_SIZE as f64 / 3.
-                + noise.get([i / 16., k / 16.]).abs() * CHUNK_SIZE as f64 / 8.;
-
-            let height = (height_float as usize).min(CHUNK_SIZE - 1);
-            let dirt_height = random_range(1..=3);
-            for local_y in 0..=height {
-                if local_y == height {
-                    chunk.voxels[local_z][local_y][local_x] = Voxel::Full(BlockType::Grass);
+            let column_x = position.x * CHUNK_SIZE as i32 + local_x as i32;
+            let column_z = position.z * CHUNK_SIZE as i32 + local_z as i32;
+            let TerrainColumn { height, biome } = terrain.column(column_x, column_z);
+            let (surface, subsurface) = blocks.layers(biome);
+            let subsurface_depth = biome.subsurface_depth() + column_rng.random_range(0..=2);
+            for local_y in 0..CHUNK_SIZE {
+                let world_y = chunk_bottom + local_y as i32;
+                if world_y > height {
+                    if world_y < SEA_LEVEL {
+                        chunk.set(local_x, local_y, local_z, blocks.water);
+                    }
+                    continue;
                 }
-                else if local_y > height.saturating_sub(dirt_height) {
-                    chunk.voxels[local_z][local_y][local_x] = Voxel::Full(BlockType::Dirt);
+                if terrain.caves.carved(IVec3::new(column_x, world_y, column_z), height) {
+                    continue;
                 }
-                else if random_bool(0.05) {
-                    chunk.voxels[local_z][local_y][local_x] = Voxel::Full(BlockType::Coal);
+
+                if world_y == height {
+                    chunk.set(local_x, local_y, local_z, surface);
+                }
+                else if world_y >= height - subsurface_depth {
+                    chunk.set(local_x, local_y, local_z, subsurface);
                 }
                 else {
-                    chunk.voxels[local_z][local_y][local_x] = Voxel::Full(BlockType::Stone);
+                    chunk.set(local_x, local_y, local_z, blocks.stone);
                 }
             }
+        }
+    }
 
-            for local_y in 0..4 {
-                if chunk.voxels[local_z][local_y][local_x] == Voxel::Empty {
-                    chunk.voxels[local_z][local_y][local_x] = Voxel::Semi(BlockType::Water);
+    // ore only ever replaces stone, so it never fills caves or shows through soil and sand, veins are no
+    // longer than a chunk so the ones reaching in started in this chunk or one right around it
+    let origin = position * CHUNK_SIZE as i32;
+    for (index, ore) in blocks.ores.iter().enumerate() {
+        for neighbor in chunk_neighborhood(-1..=1) {
+            let mut rng = terrain.rng(position + neighbor, TerrainNoise::ORE_SALT ^ index as u64);
+            for world in ore.veins(origin + neighbor * CHUNK_SIZE as i32, &mut rng) {
+                let local = world - origin;
+                if local.cmplt(IVec3::ZERO).any() || local.cmpge(IVec3::splat(CHUNK_SIZE as i32)).any() {
+                    continue;
+                }
+                let [x, y, z] = local.to_array().map(|value| value as usize);
+                if chunk.get(x, y, z) == blocks.stone {
+                    chunk.set(x, y, z, ore.voxel);
                 }
             }
         }
     }
 
+    chunk.compact();
     chunk
 }
 
+/// offsets to a chunk and the chunks around it, one chunk out sideways and within heights vertically
+fn chunk_neighborhood(heights: RangeInclusive<i32>) -> impl Iterator<Item = IVec3> {
+    heights.flat_map(|y| (-1..=1).flat_map(move |z| (-1..=1).map(move |x| IVec3::new(x, y, z))))
+}
+
+/// trees grow on a grid of cells this many columns wide, at most one to a cell and never in the last two
+/// columns or rows of it, so trees in neighboring cells always stand at least three columns apart
+const TREE_CELL: i32 = 4;
+
+/// the trees rooted in a chunk as the bottom voxel of each trunk and the trunk's height, every cell of the
+/// tree grid rolls for a tree by the density of the biome where it would stand, worked out from the seed and
+/// the columns alone so any chunk can find its neighbors' trees without generating them
+fn chunk_trees(position: IVec3, terrain: &TerrainNoise, blocks: &TerrainBlocks) -> Vec<(IVec3, i32)> {
+    let origin = position * CHUNK_SIZE as i32;
+    let cells = CHUNK_SIZE as i32 / TREE_CELL;
+    // densities are per column and a cell holds a single tree, so the chance covers the whole cell
+    let cell_area = (TREE_CELL * TREE_CELL) as f64;
+    let mut trees = Vec::new();
+
+    for cell_x in 0..cells {
+        for cell_z in 0..cells {
+            let corner = IVec3::new(origin.x + cell_x * TREE_CELL, 0, origin.z + cell_z * TREE_CELL);
+            // keyed on the cell alone, every chunk of the column agrees on where the tree would stand
+            let mut rng = terrain.rng(corner, TerrainNoise::TREE_SALT);
+            let roll: f64 = rng.random();
+            let offset =
+                IVec3::new(rng.random_range(0..TREE_CELL - 2), 0, rng.random_range(0..TREE_CELL - 2));
+            let trunk_height = rng.random_range(4..=6);
+            if roll >= Biome::MAX_TREE_DENSITY * cell_area {
+                continue;
+            }
+
+            let [column_x, column_z] = [corner.x + offset.x, corner.z + offset.z];
+            let TerrainColumn { height, biome } = terrain.column(column_x, column_z);
+            if roll >= biome.tree_density() * cell_area || height < SEA_LEVEL {
+                continue;
+            }
+            // the top of a dry column is its biome's surface block unless a cave broke through it
+            let ground = IVec3::new(column_x, height, column_z);
+            if !(origin.y..origin.y + CHUNK_SIZE as i32).contains(&height)
+                || blocks.layers(biome).0 != blocks.grass
+                || terrain.caves.carved(ground, height)
+            {
+                continue;
+            }
+            trees.push((ground + IVec3::Y, trunk_height));
+        }
+    }
+
+    trees
+}
+
+/// plants the trees of the chunk along with those of every neighbor close enough to reach into it, keeping
+/// only the voxels that land inside, so a canopy over a chunk border comes out whole whichever side is
+/// generated, saved or loaded first
+fn decorate_chunk(position: IVec3, chunk: &mut Chunk, terrain: &TerrainNoise, blocks: &TerrainBlocks) {
+    let origin = position * CHUNK_SIZE as i32;
+
+    // canopies only spread two voxels sideways and trees only grow up, so the chunks beside and below do
+    for neighbor in chunk_neighborhood(-1..=0) {
+        for (base, trunk_height) in chunk_trees(position + neighbor, terrain, blocks) {
+            for (world, voxel) in tree_voxels(base, trunk_height, blocks.wood, blocks.leaf) {
+                let local = world - origin;
+                if local.cmplt(IVec3::ZERO).any() || local.cmpge(IVec3::splat(CHUNK_SIZE as i32)).any() {
+                    continue;
+                }
+                let [x, y, z] = local.to_array().map(|value| value as usize);
+                if chunk.get(x, y, z) == Voxel::Empty {
+                    chunk.set(x, y, z, voxel);
+                }
+            }
+        }
+    }
+}
+
 fn chunk_block_break(
     mut break_event: EventReader<BlockBreakEvent>,
     mut world: ResMut<WorldChunks>,
-    query: Query<(&mut Mesh3d, &mut Collider, &ChunkMarker)>,
-    meshes: ResMut<Assets<Mesh>>,
+    registry: Res<BlockRegistry>,
 ) {
-    let mut changed_chunks = HashSet::new();
-
     for event in break_event.read() {
         let world_position = WorldPosition::get(event.position);
         let Some(chunk) = world.chunks.get_mut(&world_position.chunk_location)
@@ -153,23 +632,19 @@ fn chunk_block_break(
         };
 
         let [x, y, z] = world_position.location_in_chunk.to_array().map(|value| value as usize);
-        if chunk.voxels[z][y][x] != Voxel::Empty {
-            chunk.voxels[z][y][x] = Voxel::Empty;
-            changed_chunks.insert(world_position.chunk_location);
+        if chunk.get(x, y, z) != Voxel::Empty {
+            chunk.set(x, y, z, Voxel::Empty);
+            world.mark_edited(&world_position);
+            world.relight(&registry, |light| light.voxel_changed(event.position));
         }
     }
-
-    chunk_mesh_rebuild(world, query, meshes, changed_chunks);
 }
 
 fn chunk_block_place(
     mut place_event: EventReader<BlockPlaceEvent>,
     mut world: ResMut<WorldChunks>,
-    query: Query<(&mut Mesh3d, &mut Collider, &ChunkMarker)>,
-    meshes: ResMut<Assets<Mesh>>,
+    registry: Res<BlockRegistry>,
 ) {
-    let mut changed_chunks = HashSet::new();
-
     for event in place_event.read() {
         let world_position = WorldPosition::get(event.position);
         let Some(chunk) = world.chunks.get_mut(&world_position.chunk_location)
@@ -179,100 +654,401 @@ fn chunk_block_place(
         };
 
         let [x, y, z] = world_position.location_in_chunk.to_array().map(|value| value as usize);
-        if chunk.voxels[z][y][x] == Voxel::Empty {
-            chunk.voxels[z][y][x] = event.species;
-            changed_chunks.insert(world_position.chunk_location);
+        if event.species != Voxel::Empty && chunk.get(x, y, z) == Voxel::Empty {
+            chunk.set(x, y, z, event.species);
+            world.mark_edited(&world_position);
+            world.relight(&registry, |light| light.voxel_changed(event.position));
         }
     }
-
-    chunk_mesh_rebuild(world, query, meshes, changed_chunks);
 }
 
 fn chunk_mesh_rebuild(
-    world: ResMut<WorldChunks>,
-    mut query: Query<(&mut Mesh3d, &mut Collider, &ChunkMarker)>,
+    mut world: ResMut<WorldChunks>,
+    mut tasks: ResMut<ChunkTasks>,
+    chunks: Query<&ChunkMarker>,
+    mode: Res<MeshingMode>,
+    registry: Res<BlockRegistry>,
+) {
+    if world.dirty.is_empty() {
+        return;
+    }
+
+    let pool = AsyncComputeTaskPool::get();
+    for marker in chunks {
+        if !world.dirty.contains(&marker.location) {
+            continue;
+        }
+        let Some(padded) = world.padded_chunk(marker.location)
+        else {
+            continue;
+        };
+
+        // replacing a pending task drops it, which cancels meshing of voxels that are already stale
+        let mode = *mode;
+        let registry = registry.clone();
+        let task = pool.spawn(async move { ChunkMeshOutput::build(&padded, mode, &registry) });
+        tasks.meshing.insert(marker.location, task);
+    }
+
+    world.dirty.clear();
+}
+
+fn chunk_mesh_apply(
+    mut commands: Commands,
+    mut tasks: ResMut<ChunkTasks>,
     mut meshes: ResMut<Assets<Mesh>>,
-    changed_chunks: HashSet<IVec3>,
+    chunks: Query<(Entity, &ChunkMarker, &Children)>,
+    transparent_meshes: Query<(), With<TransparentChunkMesh>>,
+    settings: Res<Settings>,
 ) {
-    for chunk_pos in changed_chunks {
-        if let Some(chunk) = world.chunks.get(&chunk_pos) {
-            for (mut mesh, mut collider, marker) in &mut query {
-                if marker.location != chunk_pos {
-                    continue;
-                }
+    let mut applied = 0;
+    for (entity, marker, children) in chunks {
+        if applied >= settings.world.chunk_apply_budget {
+            break;
+        }
+        let Some(task) = tasks.meshing.get_mut(&marker.location)
+        else {
+            continue;
+        };
+        let Some(output) = block_on(future::poll_once(task))
+        else {
+            continue;
+        };
+        tasks.meshing.remove(&marker.location);
+        applied += 1;
 
-                let new_mesh = generate_opaque_mesh(chunk);
-                let bevy_mesh = build_mesh(&new_mesh);
-                *mesh = Mesh3d(meshes.add(bevy_mesh.clone()));
-                *collider = Collider::from_bevy_mesh(&bevy_mesh, &TRI_COLLIDER_MESH)
-                    .expect("error regenerating chunk mesh and collider");
-            }
+        let mut chunk_entity = commands.entity(entity);
+        chunk_mesh_insert(&mut chunk_entity, &mut meshes, output.opaque);
+        match output.collider {
+            | Some(collider) => chunk_entity.insert(collider),
+            | None => chunk_entity.remove::<Collider>(),
+        };
+
+        if let Some(child) = children.iter().find(|&child| transparent_meshes.contains(child)) {
+            chunk_mesh_insert(&mut commands.entity(child), &mut meshes, output.transparent);
         }
     }
 }
 
+fn chunk_mesh_insert(entity: &mut EntityCommands, meshes: &mut Assets<Mesh>, mesh: Option<Mesh>) {
+    match mesh {
+        | Some(mesh) => entity.insert(Mesh3d(meshes.add(mesh))),
+        | None => entity.remove::<Mesh3d>(),
+    };
+}
+
+fn chunk_mesher_toggle(mut mode: ResMut<MeshingMode>, mut world: ResMut<WorldChunks>, actions: Res<Actions>) {
+    if !actions.just_pressed(Action::CycleMesher) {
+        return;
+    }
+
+    *mode = match *mode {
+        | MeshingMode::Culled => MeshingMode::Greedy,
+        | MeshingMode::Greedy => MeshingMode::Culled,
+    };
+
+    let loaded_chunks: Vec<IVec3> = world.loaded.iter().copied().collect();
+    let vertices: usize = loaded_chunks
+        .iter()
+        .filter_map(|&chunk_pos| world.padded_chunk(chunk_pos))
+        .map(|padded| generate_mesh(&padded, *mode).vertex_count())
+        .sum();
+    info!("switched to {:?} meshing, {} vertices across loaded chunks", *mode, vertices);
+
+    world.dirty.extend(loaded_chunks);
+}
+
+fn chunk_memory_report(world: Res<WorldChunks>, actions: Res<Actions>) {
+    if !actions.just_pressed(Action::ReportMemory) {
+        return;
+    }
+
+    let mut total = 0;
+    for (chunk_pos, chunk) in &world.chunks {
+        let bytes = chunk.memory_usage();
+        debug!("chunk {} uses {} bytes", chunk_pos, bytes);
+        total += bytes;
+    }
+    info!(
+        "{} chunks use {} bytes, {} bytes per chunk on average",
+        world.chunks.len(),
+        total,
+        total / world.chunks.len().max(1)
+    );
+}
+
 fn chunk_load_manager(
     mut commands: Commands,
     mut world: ResMut<WorldChunks>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    chunks: Query<(Entity, &ChunkMarker)>,
+    mut tasks: ResMut<ChunkTasks>,
     block_material: Res<BlockMaterial>,
-    terrain_noise: Res<TerrainNoise>,
+    terrain: TerrainSources,
     player: Single<&Transform, With<Player>>,
+    settings: Res<Settings>,
 ) {
     let player_pos = WorldPosition::get(player.translation.as_ivec3()).chunk_location;
+    let distance = settings.world.render_distance as i32;
+    let pool = AsyncComputeTaskPool::get();
 
-    let existing_chunks: HashSet<IVec3> = chunks.iter().map(|(.., marker)| marker.location).collect();
+    for x in (player_pos.x - distance)..=(player_pos.x + distance) {
+        for z in (player_pos.z - distance)..=(player_pos.z + distance) {
+            for y in settings.world.min_chunk_y..=settings.world.max_chunk_y {
+                let chunk_pos = IVec3::new(x, y, z);
 
-    for x in (player_pos.x - RENDER_DISTANCE as i32)..=(player_pos.x + RENDER_DISTANCE as i32) {
-        for z in (player_pos.z - RENDER_DISTANCE as i32)..=(player_pos.z + RENDER_DISTANCE as i32) {
-            let chunk_pos = IVec3::new(x, 0, z);
+                if world.loaded.contains(&chunk_pos) || tasks.generating.contains_key(&chunk_pos) {
+                    continue;
+                }
 
-            if existing_chunks.contains(&chunk_pos) {
-                continue;
-            }
+                // chunks still in memory kept light that may have gone stale, so they are lit in full here,
+                // which only happens to the few whose edits haven't been saved yet
+                if world.chunks.contains_key(&chunk_pos) {
+                    chunk_spawn(&mut commands, &mut world, &block_material, chunk_pos);
+                    world.relight(&terrain.registry, |light| light.seed_chunk(chunk_pos));
+                    continue;
+                }
 
-            world.chunks.entry(chunk_pos).or_insert_with(|| generate_chunk(chunk_pos, &terrain_noise));
+                // the whole column is queued at once, lit under the sky of the chunk above where that is already
+                // loaded and under open sky otherwise, a wrong guess is corrected when the chunk is applied
+                let above = chunk_pos + IVec3::Y;
+                let sky_above = match world.loaded.contains(&above) {
+                    | true => sky_below(&world.chunks[&above]),
+                    | false => OPEN_SKY,
+                };
 
-            let chunk = world.chunks.get(&chunk_pos).expect("failed to insert generated chunk");
+                let noise = terrain.noise.clone();
+                let registry = terrain.registry.clone();
+                let blocks = terrain.blocks.clone();
+                let task = pool.spawn(async move {
+                    let generate = || {
+                        let mut chunk = generate_chunk(chunk_pos, &noise, &blocks);
+                        decorate_chunk(chunk_pos, &mut chunk, &noise, &blocks);
+                        chunk
+                    };
+                    let chunk = match load_chunk(chunk_pos, &registry) {
+                        | Ok(Some(chunk)) => chunk,
+                        | Ok(None) => generate(),
+                        | Err(err) => {
+                            error!("failed to load saved chunk {}: {}", chunk_pos, err);
+                            generate()
+                        }
+                    };
 
-            let mesh_builder = generate_opaque_mesh(chunk);
-            let mesh = build_mesh(&mesh_builder);
-            let collider = Collider::from_bevy_mesh(&mesh, &TRI_COLLIDER_MESH)
-                .expect("failed to generate rapier collider for chunk mesh");
-            let transform =
-                Transform::from_xyz(x as f32 * CHUNK_SIZE as f32, 0., z as f32 * CHUNK_SIZE as f32);
+                    LitChunk { chunk: light_chunk(chunk_pos, chunk, &sky_above, &registry), sky_above }
+                });
+                tasks.generating.insert(chunk_pos, task);
+            }
+        }
+    }
+}
 
-            commands
-                .spawn(ChunkMarker { location: chunk_pos })
-                .insert(Mesh3d(meshes.add(mesh)))
-                .insert(MeshMaterial3d(block_material.opaque_material.clone()))
-                .insert(collider)
-                .insert(RigidBody::Fixed)
-                .insert(transform);
+fn chunk_generation_apply(
+    mut commands: Commands,
+    mut world: ResMut<WorldChunks>,
+    mut tasks: ResMut<ChunkTasks>,
+    block_material: Res<BlockMaterial>,
+    registry: Res<BlockRegistry>,
+    settings: Res<Settings>,
+) {
+    let pool = AsyncComputeTaskPool::get();
+    let pending: HashSet<IVec3> = tasks.generating.keys().copied().collect();
+    let mut applied = 0;
+    tasks.generating.retain(|&chunk_pos, task| {
+        // chunks are applied from the top of a column down, so the sky falling into each one is known by then
+        let above = chunk_pos + IVec3::Y;
+        if applied >= settings.world.chunk_apply_budget || pending.contains(&above) {
+            return true;
         }
+        let Some(LitChunk { chunk, sky_above }) = block_on(future::poll_once(&mut *task))
+        else {
+            return true;
+        };
+
+        // lit under open sky before the chunk above was loaded or under a sky an edit has since changed, the
+        // chunk goes back to the pool to be lit under the sky it really gets
+        let sky = (chunk_pos.y < settings.world.max_chunk_y && world.loaded.contains(&above))
+            .then(|| sky_below(&world.chunks[&above]));
+        if let Some(sky) = sky
+            && sky != sky_above
+        {
+            let registry = registry.clone();
+            *task = pool.spawn(async move {
+                LitChunk { chunk: light_chunk(chunk_pos, chunk, &sky, &registry), sky_above: sky }
+            });
+            return true;
+        }
+
+        world.chunks.insert(chunk_pos, chunk);
+        chunk_spawn(&mut commands, &mut world, &block_material, chunk_pos);
+        // without a chunk above there is no sky to trust yet, the chunk is lit dark and the sky flows in from
+        // above once that chunk loads, otherwise only the light crossing its borders is left
+        if chunk_pos.y < settings.world.max_chunk_y && sky.is_none() {
+            world.relight(&registry, |light| light.seed_chunk(chunk_pos));
+        }
+        else {
+            world.relight_within(&registry, 0, |light| light.seed_borders(chunk_pos));
+        }
+        applied += 1;
+
+        false
+    });
+}
+
+fn chunk_spawn(
+    commands: &mut Commands,
+    world: &mut WorldChunks,
+    block_material: &BlockMaterial,
+    chunk_pos: IVec3,
+) {
+    world.loaded.insert(chunk_pos);
+    world.dirty.insert(chunk_pos);
+    world.mark_neighbors_dirty(chunk_pos);
+
+    let transform = Transform::from_translation(chunk_pos.as_vec3() * CHUNK_SIZE as f32);
+
+    commands
+        .spawn(ChunkMarker { location: chunk_pos })
+        .insert(MeshMaterial3d(block_material.opaque_material.clone()))
+        .insert(RigidBody::Fixed)
+        .insert(transform)
+        .with_child((
+            TransparentChunkMesh,
+            MeshMaterial3d(block_material.transparent_material.clone()),
+            Transform::default(),
+        ));
+}
+
+fn chunk_light_spread(mut world: ResMut<WorldChunks>, registry: Res<BlockRegistry>) {
+    if !world.light_queue.is_empty() {
+        world.relight_within(&registry, LIGHT_STEPS_PER_FRAME, |_| {});
     }
 }
 
 fn chunk_delete_manager(
     mut commands: Commands,
+    mut world: ResMut<WorldChunks>,
+    mut tasks: ResMut<ChunkTasks>,
     chunks: Query<(Entity, &ChunkMarker)>,
     player: Single<&Transform, With<Player>>,
+    settings: Res<Settings>,
 ) {
     let player_pos = WorldPosition::get(player.translation.as_ivec3()).chunk_location;
+    let distance = settings.world.render_distance as i32;
 
     let mut should_exist = HashSet::new();
-    for x in (player_pos.x - RENDER_DISTANCE as i32)..=(player_pos.x + RENDER_DISTANCE as i32) {
-        for z in (player_pos.z - RENDER_DISTANCE as i32)..=(player_pos.z + RENDER_DISTANCE as i32) {
-            should_exist.insert(IVec3::new(x, 0, z));
+    for x in (player_pos.x - distance)..=(player_pos.x + distance) {
+        for z in (player_pos.z - distance)..=(player_pos.z + distance) {
+            for y in settings.world.min_chunk_y..=settings.world.max_chunk_y {
+                should_exist.insert(IVec3::new(x, y, z));
+            }
         }
     }
 
+    // dropping a task cancels it, so chunks the player walked away from never finish generating
+    tasks.generating.retain(|chunk_pos, _| should_exist.contains(chunk_pos));
+
+    let mut unloaded = Vec::new();
     for (entity, chunk_position) in chunks {
         if !should_exist.contains(&chunk_position.location) {
             commands.entity(entity).despawn();
+            world.loaded.remove(&chunk_position.location);
+            world.mark_neighbors_dirty(chunk_position.location);
+            tasks.meshing.remove(&chunk_position.location);
+            unloaded.push(chunk_position.location);
+        }
+    }
+
+    // untouched chunks regenerate identically from the seed, edited ones stay in memory until they are saved
+    let saving = tasks.saving.as_ref().map_or(&[][..], |save| &save.chunks);
+    for chunk_pos in unloaded {
+        if !world.edited.contains(&chunk_pos) && !saving.contains(&chunk_pos) {
+            world.chunks.remove(&chunk_pos);
+        }
+    }
+}
+
+/// writes edited chunks that are no longer loaded to disk on the task pool, from a copy taken when the save
+/// starts, chunks only leave memory once their save went through, a failed save puts them back among the
+/// edited ones to be written again after a while
+fn chunk_save_manager(
+    mut world: ResMut<WorldChunks>,
+    mut tasks: ResMut<ChunkTasks>,
+    registry: Res<BlockRegistry>,
+    time: Res<Time>,
+) {
+    if let Some(save) = &mut tasks.saving {
+        let Some(result) = block_on(future::poll_once(&mut save.task))
+        else {
+            return;
+        };
+
+        let saved = std::mem::take(&mut save.chunks);
+        tasks.saving = None;
+        match result {
+            | Ok(()) => {
+                for chunk_pos in saved {
+                    if !world.loaded.contains(&chunk_pos) && !world.edited.contains(&chunk_pos) {
+                        world.chunks.remove(&chunk_pos);
+                    }
+                }
+            }
+            | Err(err) => {
+                error!("failed to save unloaded chunks: {}, keeping them in memory to retry", err);
+                world.edited.extend(saved);
+                tasks.save_retry = Some(Timer::from_seconds(SAVE_RETRY_SECONDS, TimerMode::Once));
+            }
         }
     }
+
+    if let Some(retry) = &mut tasks.save_retry {
+        if !retry.tick(time.delta()).finished() {
+            return;
+        }
+        tasks.save_retry = None;
+    }
+
+    let unloaded: Vec<(IVec3, Chunk)> = world
+        .unsaved()
+        .filter(|(chunk_pos, _)| !world.loaded.contains(chunk_pos))
+        .map(|(chunk_pos, chunk)| (chunk_pos, chunk.clone()))
+        .collect();
+    if unloaded.is_empty() {
+        return;
+    }
+
+    let chunks: Vec<IVec3> = unloaded.iter().map(|&(chunk_pos, _)| chunk_pos).collect();
+    for chunk_pos in &chunks {
+        world.edited.remove(chunk_pos);
+    }
+    let registry = registry.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        save_chunks(unloaded.iter().map(|(chunk_pos, chunk)| (*chunk_pos, chunk)), &registry)
+    });
+    tasks.saving = Some(ChunkSave { chunks, task });
+}
+
+fn chunk_save_on_exit(
+    mut exit: EventReader<AppExit>,
+    mut world: ResMut<WorldChunks>,
+    mut tasks: ResMut<ChunkTasks>,
+    registry: Res<BlockRegistry>,
+) {
+    if exit.read().next().is_none() {
+        return;
+    }
+
+    // a save still running would be cancelled on exit, so it is finished here and whatever it failed to
+    // write goes out with the rest
+    if let Some(save) = tasks.saving.take()
+        && let Err(err) = block_on(save.task)
+    {
+        error!("failed to save unloaded chunks: {}", err);
+        world.edited.extend(save.chunks);
+    }
+
+    match save_chunks(world.unsaved(), &registry) {
+        | Ok(()) => world.edited.clear(),
+        | Err(err) => error!("failed to save edited chunks: {}", err),
+    }
 }
 
 #[derive(Debug)]
@@ -312,3 +1088,181 @@ mod world_position {
         assert!(world.location_in_chunk == IVec3::new(CHUNK_SIZE as i32 - 1, 0, 0));
     }
 }
+
+#[cfg(test)]
+mod terrain_generation {
+    use super::*;
+    use crate::settings::WorldSettings;
+
+    fn without_caves() -> TerrainNoise {
+        TerrainNoise::new(0, CaveSettings { cavern_density: 0., tunnel_density: 0., ..Default::default() })
+    }
+
+    #[test]
+    fn deep_chunk_is_solid() {
+        let position = IVec3::new(0, WorldSettings::default().min_chunk_y, 0);
+        let chunk = generate_chunk(position, &without_caves(), &TerrainBlocks::default());
+        assert!(chunk.iter().all(|voxel| matches!(voxel, Voxel::Full(_))));
+    }
+
+    #[test]
+    fn sky_chunk_is_empty() {
+        let blocks = TerrainBlocks::default();
+        let position = IVec3::new(0, WorldSettings::default().max_chunk_y, 0);
+        let chunk = generate_chunk(position, &TerrainNoise::default(), &blocks);
+        assert!(chunk.iter().all(|voxel| voxel == Voxel::Empty));
+    }
+
+    #[test]
+    fn columns_continue_across_chunks() {
+        let noise = without_caves();
+        let blocks = TerrainBlocks::default();
+        let lower = generate_chunk(IVec3::new(3, 0, -2), &noise, &blocks);
+        let upper = generate_chunk(IVec3::new(3, 1, -2), &noise, &blocks);
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                if upper.get(x, 0, z) != Voxel::Empty {
+                    assert!(matches!(lower.get(x, CHUNK_SIZE - 1, z), Voxel::Full(_)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ore_veins_continue_across_borders() {
+        let terrain = without_caves();
+        let blocks = TerrainBlocks::default();
+        let position = IVec3::new(1, WorldSettings::default().min_chunk_y, 0);
+        let mut rng = terrain.rng(position, TerrainNoise::ORE_SALT);
+
+        let mut chunks = HashMap::new();
+        for world in blocks.ores[0].veins(position * CHUNK_SIZE as i32, &mut rng) {
+            let WorldPosition { chunk_location, location_in_chunk } = WorldPosition::get(world);
+            let chunk = chunks
+                .entry(chunk_location)
+                .or_insert_with(|| generate_chunk(chunk_location, &terrain, &blocks));
+            let [x, y, z] = location_in_chunk.to_array().map(|value| value as usize);
+            assert!(chunk.get(x, y, z) == blocks.ores[0].voxel);
+        }
+        assert!(chunks.len() > 1);
+    }
+
+    #[test]
+    fn unknown_ore_left_out() {
+        let ores =
+            [OreSettings { block: "mithril".to_string(), ..Default::default() }, OreSettings::default()];
+        let blocks = TerrainBlocks::new(&BlockRegistry::default(), &ores).unwrap();
+        assert!(blocks.ores.len() == 1);
+    }
+
+    #[test]
+    fn surface_follows_column_query() {
+        let noise = TerrainNoise::default();
+        let blocks = TerrainBlocks::default();
+        let position = IVec3::new(-2, 0, 5);
+        let chunk = generate_chunk(position, &noise, &blocks);
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let world = position * CHUNK_SIZE as i32 + IVec3::new(x as i32, 0, z as i32);
+                let column = noise.column(world.x, world.z);
+                if !(0..CHUNK_SIZE as i32 - 1).contains(&column.height) {
+                    continue;
+                }
+                let y = column.height as usize;
+                assert!(chunk.get(x, y, z) == blocks.layers(column.biome).0);
+                assert!(!matches!(chunk.get(x, y + 1, z), Voxel::Full(_)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod seeded_generation {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_chunk() {
+        let blocks = TerrainBlocks::default();
+        for position in [IVec3::new(0, 0, 0), IVec3::new(-4, -1, 7), IVec3::new(12, 1, -3)] {
+            let first = generate_chunk(position, &TerrainNoise::new(2293, CaveSettings::default()), &blocks);
+            let second = generate_chunk(position, &TerrainNoise::new(2293, CaveSettings::default()), &blocks);
+            assert!(first == second);
+        }
+    }
+
+    #[test]
+    fn different_seed_different_chunk() {
+        let blocks = TerrainBlocks::default();
+        let first =
+            generate_chunk(IVec3::new(0, -1, 0), &TerrainNoise::new(1, CaveSettings::default()), &blocks);
+        let second =
+            generate_chunk(IVec3::new(0, -1, 0), &TerrainNoise::new(2, CaveSettings::default()), &blocks);
+        assert!(first != second);
+    }
+
+    #[test]
+    fn chunk_rng_depends_on_position() {
+        let terrain = TerrainNoise::new(2293, CaveSettings::default());
+        let [a, b] = [IVec3::new(1, 0, 0), IVec3::new(0, 0, 1)]
+            .map(|position| terrain.rng(position, TerrainNoise::ORE_SALT).random::<u64>());
+        assert!(a != b);
+    }
+}
+
+#[cfg(test)]
+mod decoration {
+    use super::*;
+
+    fn decorated(position: IVec3) -> Chunk {
+        let terrain = TerrainNoise::new(2293, CaveSettings::default());
+        let mut chunk = generate_chunk(position, &terrain, &TerrainBlocks::default());
+        decorate_chunk(position, &mut chunk, &terrain, &TerrainBlocks::default());
+        chunk
+    }
+
+    #[test]
+    fn canopies_continue_across_borders() {
+        let terrain = TerrainNoise::new(2293, CaveSettings::default());
+        let blocks = TerrainBlocks::default();
+        let position = IVec3::new(0, 0, 1);
+        let trees = chunk_trees(position, &terrain, &blocks);
+        assert!(!trees.is_empty());
+
+        let mut chunks = HashMap::new();
+        for (base, trunk_height) in trees {
+            for (world, _) in tree_voxels(base, trunk_height, blocks.wood, blocks.leaf) {
+                let WorldPosition { chunk_location, location_in_chunk } = WorldPosition::get(world);
+                let chunk = chunks.entry(chunk_location).or_insert_with(|| decorated(chunk_location));
+                let [x, y, z] = location_in_chunk.to_array().map(|value| value as usize);
+                assert!(chunk.get(x, y, z) != Voxel::Empty);
+            }
+        }
+        assert!(chunks.len() > 1);
+    }
+
+    #[test]
+    fn trees_keep_their_distance_across_borders() {
+        let terrain = TerrainNoise::new(2293, CaveSettings::default());
+        let blocks = TerrainBlocks::default();
+        let trees: Vec<IVec3> = chunk_neighborhood(0..=0)
+            .flat_map(|position| chunk_trees(position, &terrain, &blocks))
+            .map(|(base, _)| base)
+            .collect();
+        assert!(trees.len() > 1);
+
+        for (index, a) in trees.iter().enumerate() {
+            for b in &trees[index + 1..] {
+                assert!((a.xz() - b.xz()).abs().max_element() >= 3);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_trees() {
+        let position = IVec3::new(3, 0, -5);
+        assert!(decorated(position) == decorated(position));
+        assert!(decorated(position).iter().any(|voxel| voxel == TerrainBlocks::default().wood));
+    }
+}